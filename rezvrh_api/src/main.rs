@@ -1,13 +1,64 @@
 use axum::{
-    extract::{Path, Query},
-    http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Response},
+    extract::{Path, Query, State},
+    http::{
+        header::{
+            CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+            IF_NONE_MATCH, LAST_MODIFIED, WWW_AUTHENTICATE,
+        },
+        HeaderMap, HeaderName, StatusCode,
+    },
+    response::{IntoResponse, IntoResponseParts, Response},
     routing::get,
     Json, Router,
 };
 use base64::prelude::*;
-use rezvrh_scraper::{Bakalari, Error as BakalariError, Timetable, Type, Which};
+use chrono::{DateTime, Utc};
+use rezvrh_scraper::{Bakalari, Error as BakalariError, LoginError, Timetable, Type, Which};
+use serde::de::{value::StrDeserializer, IntoDeserializer};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
+use tokio::sync::{Mutex, OnceCell};
+
+/// Default lifetime of cached timetables, overridden by `REZVRH_CACHE_TTL` (in seconds)
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Lifetime of shared Bakalari instances (login and list of objects)
+const INSTANCE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Base url and credentials
+type InstanceKey = (String, Option<(String, String)>);
+/// Time of creation and the instance, once logged in
+type Instance = (Instant, Arc<OnceCell<Bakalari>>);
+
+/// State shared by handlers
+///
+/// Bakalari instances are reused by requests with the same url and credentials,
+/// they cache timetables, so concurrent identical requests result in one upstream fetch.
+#[derive(Clone)]
+struct AppState {
+    instances: Arc<Mutex<HashMap<InstanceKey, Instance>>>,
+    cache_ttl: Duration,
+    /// School checked by `/ready`, from `REZVRH_READY_URL`
+    ready_url: Option<String>,
+}
+
+impl AppState {
+    fn from_env() -> Self {
+        let cache_ttl = std::env::var("REZVRH_CACHE_TTL")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .map_or(DEFAULT_CACHE_TTL, Duration::from_secs);
+        Self {
+            instances: Arc::new(Mutex::new(HashMap::new())),
+            cache_ttl,
+            ready_url: std::env::var("REZVRH_READY_URL").ok(),
+        }
+    }
+}
 
 // Extract basic auth from headers
 fn auth(headers: &HeaderMap) -> Option<(String, String)> {
@@ -28,11 +79,31 @@ enum ApiError {
     ScrapeError(#[from] BakalariError),
     #[error("invalid or missing selector")]
     InvalidSelector,
+    #[error("invalid timetable type")]
+    InvalidType,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
+            Self::ScrapeError(
+                err @ (BakalariError::AuthRequired
+                | BakalariError::InvalidToken
+                | BakalariError::Login(LoginError::Login(_))),
+            ) => (
+                StatusCode::UNAUTHORIZED,
+                [(WWW_AUTHENTICATE, "Basic realm=\"rezvrh\"")],
+                err.to_string(),
+            )
+                .into_response(),
+            // Upstream Bakalari failed or returned something unexpected
+            Self::ScrapeError(
+                err @ (BakalariError::Login(_)
+                | BakalariError::Request(_)
+                | BakalariError::UnknownResponse(_)
+                | BakalariError::ParseFailed(_)
+                | BakalariError::Timeout),
+            ) => (StatusCode::BAD_GATEWAY, err.to_string()).into_response(),
             Self::ScrapeError(err) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
             }
@@ -40,6 +111,163 @@ impl IntoResponse for ApiError {
             Self::InvalidSelector => {
                 (StatusCode::BAD_REQUEST, "Invalid or missing selector").into_response()
             }
+            Self::InvalidType => (
+                StatusCode::BAD_REQUEST,
+                "Invalid timetable type (expected class, teacher or room)",
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Timetable with time when it was scraped
+struct TimetableResponse {
+    timetable: Timetable,
+    fetched_at: DateTime<Utc>,
+    conditions: Conditions,
+}
+
+/// Format time as HTTP date
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Validators of client's cached copy, from `If-None-Match` and `If-Modified-Since`
+#[derive(Debug, Default)]
+struct Conditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<DateTime<Utc>>,
+}
+
+impl Conditions {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        Self {
+            if_none_match: header(IF_NONE_MATCH).map(ToOwned::to_owned),
+            if_modified_since: header(IF_MODIFIED_SINCE)
+                .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+        }
+    }
+
+    /// Whether client's copy of body with `etag`, fetched at `fetched_at`, is current
+    ///
+    /// `If-Modified-Since` is only used when there is no `If-None-Match`.
+    fn not_modified(&self, etag: &str, fetched_at: DateTime<Utc>) -> bool {
+        if let Some(tags) = &self.if_none_match {
+            return tags
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+        }
+        // HTTP dates have only whole seconds
+        self.if_modified_since
+            .is_some_and(|since| fetched_at.timestamp() <= since.timestamp())
+    }
+}
+
+/// Strong `ETag` from hash of body
+fn etag(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Respond with body and its validators, or with `304 Not Modified` if client's copy is current
+fn conditional_response(
+    conditions: &Conditions,
+    fetched_at: DateTime<Utc>,
+    parts: impl IntoResponseParts,
+    body: String,
+) -> Response {
+    let etag = etag(&body);
+    let not_modified = conditions.not_modified(&etag, fetched_at);
+    let validators = [(ETAG, etag), (LAST_MODIFIED, http_date(fetched_at))];
+    if not_modified {
+        (StatusCode::NOT_MODIFIED, validators, parts, ()).into_response()
+    } else {
+        (validators, parts, body).into_response()
+    }
+}
+
+impl IntoResponse for TimetableResponse {
+    fn into_response(self) -> Response {
+        let body = match serde_json::to_string(&self.timetable) {
+            Ok(body) => body,
+            Err(err) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+            }
+        };
+        let headers = [
+            (CONTENT_TYPE, "application/json".to_owned()),
+            (
+                HeaderName::from_static("x-fetched-at"),
+                self.fetched_at.to_rfc3339(),
+            ),
+        ];
+        conditional_response(&self.conditions, self.fetched_at, headers, body)
+    }
+}
+
+/// Timetable as iCalendar with given Content-Disposition
+fn ical_response(
+    timetable: &Timetable,
+    fetched_at: DateTime<Utc>,
+    disposition: String,
+    conditions: &Conditions,
+) -> Response {
+    let headers = [
+        (CONTENT_TYPE, "text/calendar; charset=utf-8".to_owned()),
+        (CONTENT_DISPOSITION, disposition),
+        (CACHE_CONTROL, "public, max-age=900".to_owned()),
+    ];
+    let body = timetable.to_ical(chrono_tz::Europe::Prague);
+    conditional_response(conditions, fetched_at, headers, body)
+}
+
+/// `which` path segment, `.ics` suffix (e.g. `actual.ics`) selects iCalendar instead of JSON
+struct WhichSegment {
+    which: Which,
+    ical: bool,
+}
+
+impl<'de> serde::Deserialize<'de> for WhichSegment {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let segment = String::deserialize(de)?;
+        let (which, ical) = segment
+            .strip_suffix(".ics")
+            .map_or((segment.as_str(), false), |which| (which, true));
+        let which: StrDeserializer<D::Error> = which.into_deserializer();
+        Ok(Self {
+            which: Which::deserialize(which)?,
+            ical,
+        })
+    }
+}
+
+impl WhichSegment {
+    /// Respond with timetable in requested format
+    fn respond(
+        &self,
+        (timetable, fetched_at): (Timetable, DateTime<Utc>),
+        name: &str,
+        headers: &HeaderMap,
+    ) -> Response {
+        let conditions = Conditions::from_headers(headers);
+        if self.ical {
+            ical_response(
+                &timetable,
+                fetched_at,
+                format!("attachment; filename=\"{name}-{}.ics\"", self.which),
+                &conditions,
+            )
+        } else {
+            TimetableResponse {
+                timetable,
+                fetched_at,
+                conditions,
+            }
+            .into_response()
         }
     }
 }
@@ -49,100 +277,256 @@ struct GetQuery {
     url: String,
 }
 
-async fn get_api(headers: &HeaderMap, url: &str) -> Result<Bakalari, ApiError> {
-    let url = url.parse().map_err(|_| ApiError::BadUrl)?;
-    match auth(headers) {
-        Some((username, password)) => {
-            Ok(Bakalari::from_creds_no_store((&username, &password), url).await?)
+#[derive(serde::Deserialize)]
+struct TimetableQuery {
+    url: String,
+    /// `class`, `teacher` or `room`
+    #[serde(rename = "type")]
+    typ: String,
+    name: String,
+    which: Which,
+}
+
+impl TimetableQuery {
+    /// Parse timetable type
+    fn typ(&self) -> Result<Type, ApiError> {
+        match self.typ.as_str() {
+            "class" => Ok(Type::Class),
+            "teacher" => Ok(Type::Teacher),
+            "room" => Ok(Type::Room),
+            _ => Err(ApiError::InvalidType),
         }
-        None => Ok(Bakalari::no_auth(url).await?),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ListQuery {
+    url: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// Case-insensitive name prefix
+    prefix: Option<String>,
+}
+
+impl ListQuery {
+    /// Filter and slice list of objects
+    fn apply(&self, objects: Vec<String>) -> Vec<String> {
+        let prefix = self.prefix.as_deref().map(str::to_lowercase);
+        objects
+            .into_iter()
+            .filter(|name| {
+                prefix
+                    .as_ref()
+                    .is_none_or(|prefix| name.to_lowercase().starts_with(prefix))
+            })
+            .skip(self.offset.unwrap_or(0))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+async fn get_api(state: &AppState, headers: &HeaderMap, url: &str) -> Result<Bakalari, ApiError> {
+    let key = (url.to_owned(), auth(headers));
+    let cell = {
+        let mut instances = state.instances.lock().await;
+        // Forget old instances, so that the map doesn't grow forever
+        instances.retain(|_, (created, _)| created.elapsed() < INSTANCE_TTL);
+        instances
+            .entry(key.clone())
+            .or_insert_with(|| (Instant::now(), Arc::new(OnceCell::new())))
+            .1
+            .clone()
+    };
+
+    let result = cell
+        .get_or_try_init(|| async {
+            let url = url.parse().map_err(|_| ApiError::BadUrl)?;
+            let bakalari = match key.1.clone() {
+                Some(creds) => Bakalari::from_creds(creds, url).await?,
+                None => Bakalari::no_auth(url).await?,
+            };
+            Ok::<_, ApiError>(bakalari.with_cache(state.cache_ttl))
+        })
+        .await;
+    match result {
+        Ok(bakalari) => Ok(bakalari.clone()),
+        Err(err) => {
+            // Don't keep failed instance around (e.g. wrong credentials)
+            let mut instances = state.instances.lock().await;
+            if instances
+                .get(&key)
+                .is_some_and(|(_, stored)| Arc::ptr_eq(stored, &cell))
+            {
+                instances.remove(&key);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Liveness probe, never touches upstream
+async fn health() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness probe, checks that school from `REZVRH_READY_URL` responds (if set)
+async fn ready(State(state): State<AppState>) -> Response {
+    let Some(url) = &state.ready_url else {
+        return StatusCode::OK.into_response();
+    };
+    let Ok(url) = url.parse() else {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Invalid REZVRH_READY_URL",
+        )
+            .into_response();
+    };
+    match Bakalari::probe(url).await {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(err) => (StatusCode::SERVICE_UNAVAILABLE, err.to_string()).into_response(),
     }
 }
 
 async fn get_rooms(
+    State(state): State<AppState>,
     headers: HeaderMap,
-    query: Query<GetQuery>,
+    query: Query<ListQuery>,
 ) -> Result<Json<Vec<String>>, ApiError> {
-    let bakalari = get_api(&headers, &query.url).await?;
+    let bakalari = get_api(&state, &headers, &query.url).await?;
     let classes = bakalari.get_objects(rezvrh_scraper::Type::Room);
-    Ok(Json(classes))
+    Ok(Json(query.apply(classes)))
 }
 
 async fn get_classes(
+    State(state): State<AppState>,
     headers: HeaderMap,
-    query: Query<GetQuery>,
+    query: Query<ListQuery>,
 ) -> Result<Json<Vec<String>>, ApiError> {
-    let bakalari = get_api(&headers, &query.url).await?;
+    let bakalari = get_api(&state, &headers, &query.url).await?;
     let classes = bakalari.get_objects(rezvrh_scraper::Type::Class);
-    Ok(Json(classes))
+    Ok(Json(query.apply(classes)))
 }
 
 async fn get_teachers(
+    State(state): State<AppState>,
     headers: HeaderMap,
-    query: Query<GetQuery>,
+    query: Query<ListQuery>,
 ) -> Result<Json<Vec<String>>, ApiError> {
-    let bakalari = get_api(&headers, &query.url).await?;
+    let bakalari = get_api(&state, &headers, &query.url).await?;
     let classes = bakalari.get_objects(rezvrh_scraper::Type::Teacher);
-    Ok(Json(classes))
+    Ok(Json(query.apply(classes)))
+}
+
+async fn get_objects(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    query: Query<GetQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let bakalari = get_api(&state, &headers, &query.url).await?;
+    Ok(Json(bakalari.objects_json()))
 }
 
 async fn get_class_timetable(
-    Path((class_name, which)): Path<(String, Which)>,
+    State(state): State<AppState>,
+    Path((class_name, which)): Path<(String, WhichSegment)>,
     headers: HeaderMap,
     query: Query<GetQuery>,
-) -> Result<Json<Timetable>, ApiError> {
-    let bakalari = get_api(&headers, &query.url).await?;
+) -> Result<Response, ApiError> {
+    let bakalari = get_api(&state, &headers, &query.url).await?;
     let selector = bakalari
         .get_selector(Type::Class, &class_name)
         .ok_or(ApiError::InvalidSelector)?;
-    let timetable = bakalari.get_timetable(which, &selector).await?;
-    Ok(Json(timetable))
+    let fetched = bakalari
+        .get_timetable_fetched(which.which, &selector)
+        .await?;
+    Ok(which.respond(fetched, &class_name, &headers))
 }
 
 async fn get_teacher_timetable(
-    Path((teacher_name, which)): Path<(String, Which)>,
+    State(state): State<AppState>,
+    Path((teacher_name, which)): Path<(String, WhichSegment)>,
     headers: HeaderMap,
     query: Query<GetQuery>,
-) -> Result<Json<Timetable>, ApiError> {
-    let bakalari = get_api(&headers, &query.url).await?;
+) -> Result<Response, ApiError> {
+    let bakalari = get_api(&state, &headers, &query.url).await?;
     let selector = bakalari
         .get_selector(Type::Teacher, &teacher_name)
         .ok_or(ApiError::InvalidSelector)?;
-    let timetable = bakalari.get_timetable(which, &selector).await?;
-    Ok(Json(timetable))
+    let fetched = bakalari
+        .get_timetable_fetched(which.which, &selector)
+        .await?;
+    Ok(which.respond(fetched, &teacher_name, &headers))
 }
 
 async fn get_room_timetable(
-    Path((room_name, which)): Path<(String, Which)>,
+    State(state): State<AppState>,
+    Path((room_name, which)): Path<(String, WhichSegment)>,
     headers: HeaderMap,
     query: Query<GetQuery>,
-) -> Result<Json<Timetable>, ApiError> {
-    let bakalari = get_api(&headers, &query.url).await?;
+) -> Result<Response, ApiError> {
+    let bakalari = get_api(&state, &headers, &query.url).await?;
     let selector = bakalari
         .get_selector(Type::Room, &room_name)
         .ok_or(ApiError::InvalidSelector)?;
-    let timetable = bakalari.get_timetable(which, &selector).await?;
-    Ok(Json(timetable))
+    let fetched = bakalari
+        .get_timetable_fetched(which.which, &selector)
+        .await?;
+    Ok(which.respond(fetched, &room_name, &headers))
 }
 
-/*
-async fn get_timetable(
+/// Class timetable as iCalendar feed, for subscribing from calendar apps
+async fn get_class_ical(
+    State(state): State<AppState>,
+    Path((class_name, which)): Path<(String, Which)>,
     headers: HeaderMap,
     query: Query<GetQuery>,
-) -> Result<Json<Timetable>, ApiError> {
-    let bakalari = get_api(&headers, &query).await?;
-    todo!()
+) -> Result<Response, ApiError> {
+    let bakalari = get_api(&state, &headers, &query.url).await?;
+    let selector = bakalari
+        .get_selector(Type::Class, &class_name)
+        .ok_or(ApiError::InvalidSelector)?;
+    let (timetable, fetched_at) = bakalari.get_timetable_fetched(which, &selector).await?;
+    Ok(ical_response(
+        &timetable,
+        fetched_at,
+        format!("inline; filename=\"{class_name}-{which}.ics\""),
+        &Conditions::from_headers(&headers),
+    ))
+}
+
+/// Timetable of any type, e.g. `/timetable?url=...&type=class&name=2.A&which=actual`
+async fn get_timetable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    query: Query<TimetableQuery>,
+) -> Result<TimetableResponse, ApiError> {
+    let typ = query.typ()?;
+    let bakalari = get_api(&state, &headers, &query.url).await?;
+    let selector = bakalari
+        .get_selector(typ, &query.name)
+        .ok_or(ApiError::InvalidSelector)?;
+    let (timetable, fetched_at) = bakalari
+        .get_timetable_fetched(query.which, &selector)
+        .await?;
+    Ok(TimetableResponse {
+        timetable,
+        fetched_at,
+        conditions: Conditions::from_headers(&headers),
+    })
 }
-*/
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // build our application with a single route
     let app = Router::new()
         .route("/", get(|| async { "Hello, World!" }))
+        .route("/health", get(health))
+        .route("/ready", get(ready))
         .route("/classes", get(get_classes))
         .route("/rooms", get(get_rooms))
         .route("/teachers", get(get_teachers))
+        .route("/objects", get(get_objects))
+        .route("/timetable", get(get_timetable))
         .route(
             "/timetable/class/:class_name/:which",
             get(get_class_timetable),
@@ -151,10 +535,79 @@ async fn main() -> anyhow::Result<()> {
             "/timetable/teacher/:teacher_name/:which",
             get(get_teacher_timetable),
         )
-        .route("/timetable/room/:room_name/:which", get(get_room_timetable));
+        .route("/timetable/room/:room_name/:which", get(get_room_timetable))
+        .route("/ical/class/:class_name/:which", get(get_class_ical))
+        .with_state(AppState::from_env());
 
     // run our app with hyper, listening globally on port 3000
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     axum::serve(listener, app).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{conditional_response, etag, http_date, Conditions};
+    use axum::http::{
+        header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    };
+    use chrono::{Duration, Utc};
+
+    fn status(headers: &[(HeaderName, String)]) -> StatusCode {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect::<HeaderMap>();
+        let fetched_at = Utc::now() - Duration::minutes(5);
+        conditional_response(
+            &Conditions::from_headers(&headers),
+            fetched_at,
+            (),
+            "{}".to_owned(),
+        )
+        .status()
+    }
+
+    #[test]
+    fn conditional_requests() {
+        let tag = etag("{}");
+        let now = http_date(Utc::now());
+        let before = http_date(Utc::now() - Duration::hours(1));
+
+        assert_eq!(status(&[]), StatusCode::OK);
+        assert_eq!(
+            status(&[(IF_NONE_MATCH, tag.clone())]),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(
+            status(&[(IF_NONE_MATCH, format!("\"other\", W/{tag}"))]),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(
+            status(&[(IF_NONE_MATCH, "\"other\"".to_owned())]),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&[(IF_MODIFIED_SINCE, now.clone())]),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(status(&[(IF_MODIFIED_SINCE, before)]), StatusCode::OK);
+        // If-None-Match takes precedence
+        assert_eq!(
+            status(&[
+                (IF_NONE_MATCH, "\"other\"".to_owned()),
+                (IF_MODIFIED_SINCE, now)
+            ]),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn etag_header() {
+        let response =
+            conditional_response(&Conditions::default(), Utc::now(), (), "{}".to_owned());
+        assert_eq!(response.headers()[ETAG], etag("{}").as_str());
+        assert_ne!(etag("{}"), etag("[]"));
+    }
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use inquire::Select;
 use reqwest::Url;
 use rezvrh_scraper::{Bakalari, Type, Which};
@@ -23,6 +23,52 @@ struct Args {
     /// URL of Bakalari
     #[arg(short, long, value_name = "URL")]
     url: Option<String>,
+
+    /// Type of timetable, prompted if missing
+    #[arg(short, long = "type", value_enum)]
+    typ: Option<TypeArg>,
+
+    /// Which timetable, prompted if missing
+    #[arg(short, long, value_enum)]
+    which: Option<WhichArg>,
+
+    /// Name of class, teacher or room, prompted if missing
+    #[arg(short, long, alias = "name", value_name = "NAME")]
+    object: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TypeArg {
+    Teacher,
+    Class,
+    Room,
+}
+
+impl From<TypeArg> for Type {
+    fn from(typ: TypeArg) -> Self {
+        match typ {
+            TypeArg::Teacher => Self::Teacher,
+            TypeArg::Class => Self::Class,
+            TypeArg::Room => Self::Room,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum WhichArg {
+    Permanent,
+    Actual,
+    Next,
+}
+
+impl From<WhichArg> for Which {
+    fn from(which: WhichArg) -> Self {
+        match which {
+            WhichArg::Permanent => Self::Permanent,
+            WhichArg::Actual => Self::Actual,
+            WhichArg::Next => Self::Next,
+        }
+    }
 }
 
 #[tokio::main]
@@ -46,20 +92,37 @@ async fn main() -> anyhow::Result<()> {
 
     bakalari.test().await?;
 
-    let typ = Select::new("Choose type", vec![Type::Teacher, Type::Class, Type::Room]).prompt()?;
-    let which = Select::new(
-        "Choose which",
-        vec![Which::Permanent, Which::Actual, Which::Next],
-    )
-    .prompt()?;
+    // Prompt only for what wasn't given by arguments
+    let typ = match args.typ {
+        Some(typ) => typ.into(),
+        None => {
+            Select::new("Choose type", vec![Type::Teacher, Type::Class, Type::Room]).prompt()?
+        }
+    };
+    let which = match args.which {
+        Some(which) => which.into(),
+        None => Select::new(
+            "Choose which",
+            vec![Which::Permanent, Which::Actual, Which::Next],
+        )
+        .prompt()?,
+    };
 
-    let mut options = bakalari.get_objects(typ);
-    options.sort();
-    let select = Select::new("Choose object", options).prompt()?;
-    let selection = bakalari.get_selector(typ, &select).unwrap();
+    let selection = if let Some(object) = args.object {
+        bakalari
+            .get_selector(typ, &object)
+            .ok_or_else(|| anyhow::anyhow!("Unknown {typ}: {object}"))?
+    } else {
+        let mut options = bakalari.get_objects(typ);
+        options.sort();
+        let select = Select::new("Choose object", options).prompt()?;
+        bakalari.get_selector(typ, &select).unwrap()
+    };
 
     let table = bakalari.get_timetable(which, &selection).await?;
 
+    print!("{}", table.to_ascii());
+
     fs::write("timetable.json", serde_json::to_string_pretty(&table)?).await?;
 
     println!("Wrote timetable to timetable.json");
@@ -0,0 +1,29 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rezvrh_scraper::{Selector, Timetable};
+use std::hint::black_box;
+
+const FIXTURES: [(&str, &str); 3] = [
+    ("class", include_str!("../fixtures/class.html")),
+    ("teacher", include_str!("../fixtures/teacher.html")),
+    ("room", include_str!("../fixtures/room.html")),
+];
+
+/// Parse saved timetable pages, throughput is in bytes of html
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("from_html");
+    for (name, html) in FIXTURES {
+        let selector = match name {
+            "teacher" => Selector::Teacher("UXYZ".to_owned()),
+            "room" => Selector::Room("R1".to_owned()),
+            _ => Selector::Class("ZA".to_owned()),
+        };
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| Timetable::from_html(black_box(html), &selector).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -1,9 +1,35 @@
 mod modules;
+pub mod prelude;
 
+pub use modules::api::BakalariApi;
+pub use modules::auth::AuthInput;
+pub use modules::auth::LoginError;
+pub use modules::auth::TOKEN_LIFETIME;
 pub use modules::bakalari::Bakalari;
+pub use modules::bakalari::Client;
+pub use modules::bakalari::FetchedTimetable;
 pub use modules::bakalari::RequestError as Error;
+pub use modules::bakalari::PERMANENT_CACHE_TTL;
+pub use modules::bakalari::{AccessMode, SelfCheckReport};
+pub use modules::bakalari::{ClientConfig, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+pub use modules::bakalari::{Transport, TransportFuture, TransportResponse};
+pub use modules::timetable::common_free_periods;
+pub use modules::timetable::diff;
+pub use modules::timetable::ChangeInfo;
+pub use modules::timetable::Day;
+pub use modules::timetable::Delta;
+pub use modules::timetable::Event;
+pub use modules::timetable::Group;
+pub use modules::timetable::Hour;
 pub use modules::timetable::Lesson;
+pub use modules::timetable::LessonChange;
+pub use modules::timetable::Normalize;
+pub use modules::timetable::ParseError;
 pub use modules::timetable::RawType as Type;
+pub use modules::timetable::ScheduleMismatch;
 pub use modules::timetable::Timetable;
 pub use modules::timetable::Type as Selector;
+pub use modules::timetable::ValidationError;
 pub use modules::timetable::Which;
+pub use modules::timetable::{CellPatch, TimetablePatch};
+pub use modules::timetable::{DayHours, HoursReport};
@@ -1,3 +1,4 @@
+pub mod api;
 pub mod auth;
 pub mod bakalari;
 pub mod timetable;
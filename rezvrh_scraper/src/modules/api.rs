@@ -0,0 +1,415 @@
+use self::model::{ApiAtom, ApiEntity, ApiTimetable};
+use super::auth::{ApiSession, ApiToken, Credentials};
+use super::bakalari::{Client, RequestError, RequestResult, TransportResponse};
+use super::timetable::{ChangeInfo, Day, Hour, Lesson, Timetable, Which};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveTime, Weekday};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    StatusCode, Url,
+};
+use std::collections::HashMap;
+
+mod model;
+
+/// Client of official Bakalari JSON API (`/api/3`)
+///
+/// Alternative to scraping for schools that allow API access. The API only serves
+/// timetable of the logged in user, so there are no object lists or selectors.
+/// Access token is renewed with refresh token once it expires.
+/// Requests go through client's transport and retry policy.
+#[derive(Debug)]
+pub struct BakalariApi {
+    client: Client,
+    session: ApiSession,
+}
+
+impl BakalariApi {
+    /// Log in to API with username and password
+    ///
+    /// API tokens are only used by this client, scraped pages authenticate by cookie.
+    ///
+    /// # Errors
+    /// If login fails, [`LoginError::ApiUnavailable`] if school doesn't expose the API
+    /// (cookie login of [`crate::Bakalari::from_creds`] can be used instead)
+    ///
+    /// [`LoginError::ApiUnavailable`]: crate::LoginError::ApiUnavailable
+    pub async fn login((username, password): (&str, &str), url: Url) -> RequestResult<Self> {
+        Self::login_with_client((username, password), Client::new(url)).await
+    }
+
+    /// Log in to API with username and password, using configured client
+    ///
+    /// # Errors
+    /// If login fails
+    pub async fn login_with_client(
+        (username, password): (&str, &str),
+        client: Client,
+    ) -> RequestResult<Self> {
+        let token = Credentials::login_api((username, password), &client).await?;
+        Ok(Self::from_api_token(token, client))
+    }
+
+    /// Use tokens from previous API login
+    #[must_use]
+    pub fn from_api_token(token: ApiToken, client: Client) -> Self {
+        Self {
+            client,
+            session: ApiSession::new(token),
+        }
+    }
+
+    /// Send GET request with current access token
+    ///
+    /// Returns response together with access token that was used.
+    async fn get(&self, path: &str) -> RequestResult<(TransportResponse, String)> {
+        let access_token = self.session.access_token(&self.client).await?;
+        let mut headers = HeaderMap::new();
+        let authorization = HeaderValue::from_str(&format!("Bearer {access_token}"))
+            .map_err(|_| RequestError::InvalidToken)?;
+        headers.insert(AUTHORIZATION, authorization);
+        let res = self.client.get_with_headers(path, None, headers).await?;
+        Ok((res, access_token))
+    }
+
+    /// Get timetable of logged in user
+    ///
+    /// Actual and next week are relative to current local date.
+    ///
+    /// # Errors
+    /// If request fails or response isn't valid timetable
+    pub async fn get_timetable(&self, which: Which) -> RequestResult<Timetable> {
+        let today = Local::now().date_naive();
+        let path = match which {
+            Which::Permanent => "api/3/timetable/permanent".to_owned(),
+            Which::Actual => format!("api/3/timetable/actual?date={today}"),
+            Which::Next => format!("api/3/timetable/actual?date={}", today + Duration::days(7)),
+        };
+
+        let (mut res, access_token) = self.get(&path).await?;
+        if res.status == StatusCode::UNAUTHORIZED {
+            // Access token expired, renew it and retry once
+            self.session.refresh(&access_token, &self.client).await?;
+            res = self.get(&path).await?.0;
+        }
+        if res.status == StatusCode::UNAUTHORIZED {
+            return Err(RequestError::AuthRequired);
+        }
+        let timetable = serde_json::from_str::<ApiTimetable>(&res.body)
+            .map_err(|_| RequestError::UnknownResponse("invalid timetable response"))?;
+
+        Ok(convert(&timetable))
+    }
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Entities by id
+fn by_id(entities: &[ApiEntity]) -> HashMap<&str, &ApiEntity> {
+    entities
+        .iter()
+        .map(|entity| (entity.id.trim(), entity))
+        .collect()
+}
+
+/// Convert API timetable to the same structure as scraped one
+fn convert(timetable: &ApiTimetable) -> Timetable {
+    let classes = by_id(&timetable.classes);
+    let subjects = by_id(&timetable.subjects);
+    let teachers = by_id(&timetable.teachers);
+    let rooms = by_id(&timetable.rooms);
+    let cycles = by_id(&timetable.cycles);
+    let groups = timetable
+        .groups
+        .iter()
+        .map(|group| (group.id.trim(), group))
+        .collect::<HashMap<_, _>>();
+
+    let lesson = |period: usize, atom: &ApiAtom| -> Option<Lesson> {
+        let subject = atom
+            .subject_id
+            .as_deref()
+            .and_then(|id| subjects.get(id.trim()));
+        let teacher = atom
+            .teacher_id
+            .as_deref()
+            .and_then(|id| teachers.get(id.trim()));
+        let room = atom
+            .room_id
+            .as_deref()
+            .and_then(|id| rooms.get(id.trim()))
+            .map(|room| room.abbrev.trim().to_owned());
+
+        if let Some(change) = &atom.change {
+            if change.change_type == "Canceled" || change.change_type == "Removed" {
+                return Some(Lesson::Canceled {
+                    period,
+                    subject: subject.map(|subject| subject.name.trim().to_owned()),
+                    teacher: teacher.map(|teacher| teacher.name.trim().to_owned()),
+                    room,
+                });
+            }
+        }
+
+        // Atoms without subject are only notes (e.g. school events)
+        let subject = subject?;
+        let atom_groups = atom
+            .group_ids
+            .iter()
+            .filter_map(|id| groups.get(id.trim()))
+            .collect::<Vec<_>>();
+        let class = atom_groups
+            .iter()
+            .filter_map(|group| classes.get(group.class_id.trim()))
+            .map(|class| class.abbrev.trim())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let group = atom_groups
+            .iter()
+            .map(|group| group.abbrev.trim())
+            .filter(|abbrev| !abbrev.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let cycle = atom
+            .cycle_ids
+            .iter()
+            .filter_map(|id| cycles.get(id.trim()))
+            .map(|cycle| cycle.name.trim())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let non_empty = |s: String| (!s.is_empty()).then_some(s);
+
+        let subject_name = subject.name.trim().to_owned();
+        let abbr = subject.abbrev.trim().to_owned();
+        let teacher_name = teacher.map_or_else(String::new, |t| t.name.trim().to_owned());
+        let teacher_abbr = teacher.map(|t| t.abbrev.trim().to_owned());
+        let group = non_empty(group);
+        let topic = atom.theme.clone().and_then(non_empty);
+        let cycle = non_empty(cycle);
+
+        Some(match &atom.change {
+            Some(change) => Lesson::Substitution {
+                period,
+                class,
+                subject: subject_name,
+                abbr,
+                date: None,
+                time: None,
+                teacher: teacher_name,
+                teacher_abbr,
+                additional_teachers: Vec::new(),
+                room,
+                group,
+                topic,
+                homeworks: None,
+                cycle,
+                notice: None,
+                change_info: change.description.clone(),
+                has_absent: false,
+                absence_text: None,
+                absent_info_text: None,
+                change: change.description.as_deref().map(ChangeInfo::parse),
+            },
+            None => Lesson::Regular {
+                period,
+                class,
+                subject: subject_name,
+                abbr,
+                date: None,
+                time: None,
+                teacher: teacher_name,
+                teacher_abbr,
+                additional_teachers: Vec::new(),
+                room,
+                group,
+                topic,
+                homeworks: None,
+                cycle,
+                notice: None,
+                change_info: None,
+                has_absent: false,
+                absence_text: None,
+                absent_info_text: None,
+            },
+        })
+    };
+
+    let hours = timetable
+        .hours
+        .iter()
+        .filter_map(|hour| {
+            let start = NaiveTime::parse_from_str(hour.begin_time.trim(), "%H:%M").ok()?;
+            let end = NaiveTime::parse_from_str(hour.end_time.trim(), "%H:%M").ok()?;
+            let duration = u32::try_from((end - start).num_minutes()).ok()?;
+            let num = hour
+                .caption
+                .trim()
+                .parse()
+                .ok()
+                .or_else(|| usize::try_from(hour.id).ok())?;
+            Some((hour.id, Hour::new(num, start, duration)))
+        })
+        .collect::<Vec<_>>();
+
+    let days = timetable
+        .days
+        .iter()
+        .map(|day| {
+            let lessons = hours
+                .iter()
+                .enumerate()
+                .map(|(period, (id, _))| {
+                    day.atoms
+                        .iter()
+                        .filter(|atom| atom.hour_id == *id)
+                        .filter_map(|atom| lesson(period, atom))
+                        .collect()
+                })
+                .collect();
+            let date = day
+                .date
+                .as_deref()
+                .and_then(|date| date.get(..10))
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+            let weekday = date.map(|date| date.weekday()).or_else(|| {
+                let index = usize::try_from(day.day_of_week.checked_sub(1)?).ok()?;
+                WEEKDAYS.get(index).copied()
+            });
+            Day {
+                date,
+                weekday,
+                lessons,
+                events: Vec::new(),
+            }
+        })
+        .collect();
+
+    Timetable::new(hours.into_iter().map(|(_, hour)| hour).collect(), days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert, model::ApiTimetable};
+    use crate::{ChangeInfo, Lesson, Timetable};
+    use chrono::{NaiveDate, NaiveTime, Weekday};
+
+    fn timetable() -> Timetable {
+        let timetable: ApiTimetable =
+            serde_json::from_str(include_str!("../../fixtures/api-timetable.json")).unwrap();
+        convert(&timetable)
+    }
+
+    #[test]
+    fn hours() {
+        let timetable = timetable();
+        let starts = timetable
+            .hours
+            .iter()
+            .map(|hour| (hour.num, hour.start, hour.duration))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            [
+                (1, NaiveTime::from_hms_opt(8, 0, 0).unwrap(), 45),
+                (2, NaiveTime::from_hms_opt(8, 55, 0).unwrap(), 45),
+                (3, NaiveTime::from_hms_opt(10, 0, 0).unwrap(), 45),
+            ]
+        );
+        assert_eq!(timetable.days.len(), 2);
+        assert_eq!(timetable.days[0].date, NaiveDate::from_ymd_opt(2023, 9, 4));
+        assert_eq!(timetable.days[0].weekday, Some(Weekday::Mon));
+        assert!(timetable.validate().is_ok());
+    }
+
+    #[test]
+    fn regular() {
+        let timetable = timetable();
+        let Lesson::Regular {
+            period,
+            class,
+            subject,
+            abbr,
+            teacher,
+            teacher_abbr,
+            room,
+            group,
+            topic,
+            cycle,
+            ..
+        } = &timetable.days[0].lessons[0][0]
+        else {
+            panic!("expected regular lesson");
+        };
+        assert_eq!(*period, 0);
+        assert_eq!(class, "1.A");
+        assert_eq!(subject, "Matematika");
+        assert_eq!(abbr, "M");
+        assert_eq!(teacher, "Abrahám Karel");
+        assert_eq!(teacher_abbr.as_deref(), Some("Ab"));
+        assert_eq!(room.as_deref(), Some("101"));
+        assert_eq!(*group, None);
+        assert_eq!(topic.as_deref(), Some("Lineární rovnice"));
+        assert_eq!(*cycle, None);
+    }
+
+    #[test]
+    fn substitution() {
+        let timetable = timetable();
+        let Lesson::Substitution {
+            period,
+            subject,
+            teacher,
+            room,
+            topic,
+            cycle,
+            change_info,
+            change,
+            ..
+        } = &timetable.days[0].lessons[1][0]
+        else {
+            panic!("expected substitution");
+        };
+        assert_eq!(*period, 1);
+        assert_eq!(subject, "Fyzika");
+        assert_eq!(teacher, "Xylofonová Eva");
+        assert_eq!(room.as_deref(), Some("Fyz"));
+        assert_eq!(*topic, None);
+        assert_eq!(cycle.as_deref(), Some("Sudý týden"));
+        assert_eq!(
+            change_info.as_deref(),
+            Some("Suplování: Novák Jan (Matematika)")
+        );
+        assert_eq!(
+            *change,
+            Some(ChangeInfo::parse("Suplování: Novák Jan (Matematika)"))
+        );
+    }
+
+    #[test]
+    fn canceled() {
+        let timetable = timetable();
+        assert_eq!(
+            timetable.days[0].lessons[2],
+            [Lesson::Canceled {
+                period: 2,
+                subject: Some("Chemie".to_owned()),
+                teacher: Some("Abrahám Karel".to_owned()),
+                room: Some("101".to_owned()),
+            }]
+        );
+    }
+
+    #[test]
+    fn note_without_subject() {
+        let timetable = timetable();
+        assert_eq!(timetable.days[1].weekday, Some(Weekday::Tue));
+        assert!(timetable.days[1].lessons.iter().all(Vec::is_empty));
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Deserialize;
+
+/// Timetable returned by `/api/3/timetable/...`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiTimetable {
+    pub hours: Vec<ApiHour>,
+    pub days: Vec<ApiDay>,
+    #[serde(default)]
+    pub classes: Vec<ApiEntity>,
+    #[serde(default)]
+    pub groups: Vec<ApiGroup>,
+    #[serde(default)]
+    pub subjects: Vec<ApiEntity>,
+    #[serde(default)]
+    pub teachers: Vec<ApiEntity>,
+    #[serde(default)]
+    pub rooms: Vec<ApiEntity>,
+    #[serde(default)]
+    pub cycles: Vec<ApiEntity>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiHour {
+    pub id: i64,
+    pub caption: String,
+    pub begin_time: String,
+    pub end_time: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiDay {
+    pub atoms: Vec<ApiAtom>,
+    pub day_of_week: u32,
+    /// Date with time and offset, missing on permanent timetable
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiAtom {
+    pub hour_id: i64,
+    #[serde(default)]
+    pub group_ids: Vec<String>,
+    pub subject_id: Option<String>,
+    pub teacher_id: Option<String>,
+    pub room_id: Option<String>,
+    #[serde(default)]
+    pub cycle_ids: Vec<String>,
+    pub change: Option<ApiChange>,
+    pub theme: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiChange {
+    pub change_type: String,
+    pub description: Option<String>,
+}
+
+/// Class, subject, teacher, room or cycle
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiEntity {
+    pub id: String,
+    pub abbrev: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiGroup {
+    pub id: String,
+    pub class_id: String,
+    pub abbrev: String,
+}
@@ -1,10 +1,10 @@
 use super::bakalari::Client;
-use reqwest::Response;
-use std::borrow::Cow;
+use reqwest::{Response, StatusCode};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
-pub use credentials::Credentials;
+pub use credentials::{ApiSession, ApiToken, Credentials, TOKEN_LIFETIME};
 
 mod credentials;
 
@@ -16,14 +16,51 @@ pub enum LoginError {
     Request(#[from] reqwest::Error),
     /// Login error (probably wrong credentials)
     #[error("login failed")]
-    Login(Response),
+    Login(Box<Response>),
+    /// Server failed to process login (5xx), credentials might be valid
+    #[error("server error during login: {0}")]
+    Server(StatusCode),
     /// Parsing of cookei from resposne failed
     #[error("failed to parse cookie")]
     CookieParse,
+    /// Parsing of API token response failed
+    #[error("failed to parse token response")]
+    TokenParse,
+    /// School doesn't expose API login endpoint
+    #[error("API login isn't available")]
+    ApiUnavailable,
+    /// Login redirected to invalid location
+    #[error("invalid login redirect")]
+    Redirect,
+    /// Base url can't be joined with login path
+    #[error("invalid url")]
+    BadUrl,
+    /// Login recently failed, not retrying yet
+    #[error("login failed recently, retrying later")]
+    Backoff,
+    /// Background task keeping the token has stopped
+    #[error("token worker stopped")]
+    WorkerGone,
+    /// Semaphore limiting concurrent requests was closed
+    #[error("concurrency limit closed")]
+    LimitClosed(#[from] tokio::sync::AcquireError),
 }
 
 pub type LoginResult<T> = Result<T, LoginError>;
 
+/// Authentication provided by user
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AuthInput {
+    /// Username and password, token is renewed when it expires
+    Password { username: String, password: String },
+    /// Token (`BakaAuth` cookie value) from previous login, might expire
+    Token(String),
+    /// Whole Cookie header obtained from SSO login, might expire
+    SsoCookie(String),
+    /// No authentication
+    None,
+}
+
 /// Authentication types
 #[derive(Debug, Clone)]
 pub enum Auth {
@@ -31,19 +68,33 @@ pub enum Auth {
     Credentials(Credentials),
     // Token (might expire)
     Token(String),
+    // Whole cookie header (might expire)
+    Cookie(String),
     None,
 }
 
 impl Auth {
-    /// Get api token
+    /// Get value of Cookie header for requests, `None` without authentication
     ///
     /// # Errors
     /// If token renew fails
-    pub async fn get_token(&self, client: Arc<Client>) -> LoginResult<Cow<'_, String>> {
+    pub async fn get_cookie(&self, client: Arc<Client>) -> LoginResult<Option<String>> {
+        match self {
+            Self::Credentials(creds) => {
+                Ok(Some(format!("BakaAuth={}", creds.get_token(client).await?)))
+            }
+            Self::Token(token) => Ok(Some(format!("BakaAuth={token}"))),
+            Self::Cookie(cookie) => Ok(Some(cookie.to_owned())),
+            Self::None => Ok(None),
+        }
+    }
+
+    /// Get lifetime of token, `None` if token isn't renewed
+    #[must_use]
+    pub const fn token_lifetime(&self) -> Option<Duration> {
         match self {
-            Self::Token(token) => Ok(Cow::Borrowed(token)),
-            Self::Credentials(creds) => Ok(Cow::Owned(creds.get_token(client.clone()).await?)),
-            Self::None => Ok(Cow::Owned(String::new())),
+            Self::Credentials(creds) => Some(creds.token_lifetime()),
+            Self::Token(_) | Self::Cookie(_) | Self::None => None,
         }
     }
 
@@ -55,6 +106,20 @@ impl Auth {
         Ok(Self::Credentials(Credentials::new(creds, client).await?))
     }
 
+    /// Create auth from username and password, token is renewed after `lifetime`
+    ///
+    /// # Errors
+    /// If login fails
+    pub async fn from_creds_with_lifetime(
+        creds: (String, String),
+        client: &Client,
+        lifetime: Duration,
+    ) -> LoginResult<Self> {
+        Ok(Self::Credentials(
+            Credentials::new_with_lifetime(creds, client, lifetime).await?,
+        ))
+    }
+
     /// Create auth from token
     ///
     #[must_use]
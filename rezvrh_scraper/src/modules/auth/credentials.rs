@@ -1,30 +1,43 @@
 use super::{LoginError, LoginResult};
 use crate::modules::bakalari::Client;
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
 use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, Mutex};
 
 /// Struct to hold token that expires after certain time
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct TempToken {
     token: String,
     expiration: Instant,
+    refresh_at: Instant,
 }
 
-/// Lifetime of [`TempToken`] in seconds
-const TOKEN_LIFETIME: u64 = 60 * 5;
+/// Default lifetime of [`TempToken`]
+pub const TOKEN_LIFETIME: Duration = Duration::from_secs(60 * 5);
+/// How long before expiration the token is renewed in background
+const REFRESH_BEFORE: Duration = Duration::from_secs(30);
 
 impl TempToken {
-    /// Create token with expiration [`TOKEN_LIFETIME`]
-    fn new(token: String) -> Self {
+    /// Create token that expires after `lifetime`
+    fn new(token: String, lifetime: Duration) -> Self {
+        let now = Instant::now();
         Self {
             token,
-            expiration: Instant::now() + Duration::from_secs(TOKEN_LIFETIME),
+            expiration: now + lifetime,
+            // Short lived tokens are renewed in second half of their lifetime
+            refresh_at: now + lifetime - REFRESH_BEFORE.min(lifetime / 2),
         }
     }
 
+    /// Whether token should be renewed in background
+    fn expires_soon(&self) -> bool {
+        Instant::now() > self.refresh_at
+    }
+
     /// Whether token is expired
     fn expired(&self) -> bool {
         Instant::now() > self.expiration
@@ -39,86 +52,416 @@ impl TempToken {
     }
 }
 
+/// Tokens issued by API login (`/api/login`)
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize)]
+pub struct ApiToken {
+    /// Bearer token for `/api/3` requests
+    pub access_token: String,
+    /// Token for renewing access token without password
+    pub refresh_token: String,
+    /// Lifetime of access token in seconds, if server sent it
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+}
+
+/// Access token is renewed this long before it expires
+const API_REFRESH_BEFORE: Duration = Duration::from_secs(60);
+
+/// API token pair shared by clones, renewed with refresh token
+#[derive(Debug, Clone)]
+pub struct ApiSession {
+    /// Token and time after which it should be renewed
+    token: Arc<Mutex<(ApiToken, Option<Instant>)>>,
+}
+
+impl ApiSession {
+    /// Create session from token pair obtained just now
+    #[must_use]
+    pub fn new(token: ApiToken) -> Self {
+        let refresh_at = Self::refresh_at(&token);
+        Self {
+            token: Arc::new(Mutex::new((token, refresh_at))),
+        }
+    }
+
+    /// When token should be renewed, `None` if its lifetime is unknown
+    fn refresh_at(token: &ApiToken) -> Option<Instant> {
+        let lifetime = Duration::from_secs(token.expires_in?);
+        Some(Instant::now() + lifetime - API_REFRESH_BEFORE.min(lifetime / 2))
+    }
+
+    /// Get access token, renewing it first if it's about to expire
+    ///
+    /// # Errors
+    /// If renew fails
+    pub async fn access_token(&self, client: &Client) -> LoginResult<String> {
+        let mut token = self.token.lock().await;
+        if token.1.is_some_and(|at| Instant::now() > at) {
+            let renewed = Credentials::refresh_api(&token.0.refresh_token, client).await?;
+            *token = (renewed.clone(), Self::refresh_at(&renewed));
+        }
+        Ok(token.0.access_token.clone())
+    }
+
+    /// Renew access token, unless it was already renewed since `stale` was used
+    ///
+    /// # Errors
+    /// If renew fails
+    pub async fn refresh(&self, stale: &str, client: &Client) -> LoginResult<()> {
+        let mut token = self.token.lock().await;
+        if token.0.access_token == stale {
+            let renewed = Credentials::refresh_api(&token.0.refresh_token, client).await?;
+            *token = (renewed.clone(), Self::refresh_at(&renewed));
+        }
+        Ok(())
+    }
+}
+
+/// Delay before login is retried after first failure
+const BACKOFF_INITIAL: Duration = Duration::from_secs(5);
+/// Maximal delay before login is retried after repeated failures
+const BACKOFF_MAX: Duration = Duration::from_secs(60 * 5);
+
+/// Exponential backoff of failed logins
+#[derive(Debug, Default)]
+struct Backoff {
+    delay: Option<Duration>,
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    /// Whether login shouldn't be attempted yet
+    fn active(&self) -> bool {
+        self.retry_at.is_some_and(|at| Instant::now() < at)
+    }
+
+    /// Record failed login, doubling the delay
+    fn fail(&mut self) {
+        let delay = self
+            .delay
+            .map_or(BACKOFF_INITIAL, |delay| (delay * 2).min(BACKOFF_MAX));
+        self.delay = Some(delay);
+        self.retry_at = Some(Instant::now() + delay);
+    }
+}
+
 type TokenRequest = (Arc<Client>, oneshot::Sender<LoginResult<String>>);
 
+/// Message to background task of [`Credentials`]
+enum Message {
+    /// Token requested by user
+    Get(TokenRequest),
+    /// Login in background finished
+    LoggedIn(LoginResult<String>),
+}
+
 /// Struct that hold the credentials and token
+///
+/// Token is kept by a background task, which owns the credentials.
+/// Token is renewed in background shortly before it expires, at most one login runs at a time.
+/// The task runs only while some clone of this struct exists,
+/// it shuts down once the last one is dropped (even if the construction
+/// of [`crate::Bakalari`] was cancelled).
 #[derive(Debug, Clone)]
 pub struct Credentials {
-    sender: mpsc::Sender<TokenRequest>,
+    sender: mpsc::Sender<Message>,
+    lifetime: Duration,
 }
 
 impl Credentials {
-    /// Create new credentials from username and password
+    /// Create new credentials from username and password, token lives [`TOKEN_LIFETIME`]
+    ///
+    /// # Errors
+    /// If login fails
+    pub async fn new(creds: (String, String), client: &Client) -> LoginResult<Self> {
+        Self::new_with_lifetime(creds, client, TOKEN_LIFETIME).await
+    }
+
+    /// Create new credentials from username and password, token is renewed after `lifetime`
     ///
     /// # Errors
     /// If login fails
-    pub async fn new((username, password): (String, String), client: &Client) -> LoginResult<Self> {
-        let token = TempToken::new(Self::login((&username, &password), client).await?);
+    pub async fn new_with_lifetime(
+        (username, password): (String, String),
+        client: &Client,
+        lifetime: Duration,
+    ) -> LoginResult<Self> {
+        let token = TempToken::new(Self::login((&username, &password), client).await?, lifetime);
 
-        let (sender, mut receiver) = mpsc::channel::<TokenRequest>(10);
+        let (sender, mut receiver) = mpsc::channel::<Message>(10);
+        // Weak, so that the task doesn't keep itself alive
+        let weak = sender.downgrade();
+        let creds = Arc::new((username, password));
 
         tokio::spawn(async move {
             let mut store = token;
+            let mut backoff = Backoff::default();
+            let mut logging_in = false;
+            // Requesters waiting for running login
+            let mut waiting = Vec::<oneshot::Sender<LoginResult<String>>>::new();
 
-            while let Some((client, sender)) = receiver.recv().await {
-                let token = if let Some(token) = store.get() {
-                    Ok(token.to_owned())
-                } else {
-                    let token = Self::login((&username, &password), &client).await;
-                    token.map(|token| {
-                        store = TempToken::new(token);
-                        store.token.clone()
-                    })
-                };
-                sender.send(token).unwrap();
+            while let Some(message) = receiver.recv().await {
+                match message {
+                    Message::Get((client, sender)) => {
+                        if let Some(token) = store.get() {
+                            if store.expires_soon() && !logging_in && !backoff.active() {
+                                logging_in = Self::spawn_login(&weak, &creds, client);
+                            }
+                            // Requester might have been cancelled, nothing to do then
+                            let _ = sender.send(Ok(token.to_owned()));
+                        } else if logging_in {
+                            waiting.push(sender);
+                        } else if backoff.active() {
+                            // Don't storm the server with logins during an outage
+                            let _ = sender.send(Err(LoginError::Backoff));
+                        } else {
+                            logging_in = Self::spawn_login(&weak, &creds, client);
+                            waiting.push(sender);
+                        }
+                    }
+                    Message::LoggedIn(result) => {
+                        logging_in = false;
+                        match result {
+                            Ok(token) => {
+                                backoff = Backoff::default();
+                                store = TempToken::new(token, lifetime);
+                                for sender in waiting.drain(..) {
+                                    let _ = sender.send(Ok(store.token.clone()));
+                                }
+                            }
+                            Err(err) => {
+                                backoff.fail();
+                                let mut waiting = waiting.drain(..);
+                                if let Some(sender) = waiting.next() {
+                                    let _ = sender.send(Err(err));
+                                }
+                                for sender in waiting {
+                                    let _ = sender.send(Err(LoginError::Backoff));
+                                }
+                            }
+                        }
+                    }
+                }
             }
         });
 
-        Ok(Self { sender })
+        Ok(Self { sender, lifetime })
+    }
+
+    /// Log in in background, result is sent back to the task
+    ///
+    /// Returns whether login was started
+    fn spawn_login(
+        weak: &mpsc::WeakSender<Message>,
+        creds: &Arc<(String, String)>,
+        client: Arc<Client>,
+    ) -> bool {
+        let Some(sender) = weak.upgrade() else {
+            return false;
+        };
+        let creds = Arc::clone(creds);
+        tokio::spawn(async move {
+            let result = Self::login((&creds.0, &creds.1), &client).await;
+            let _ = sender.send(Message::LoggedIn(result)).await;
+        });
+        true
+    }
+
+    /// Get lifetime of token, after which it is renewed
+    #[must_use]
+    pub const fn token_lifetime(&self) -> Duration {
+        self.lifetime
     }
 
     /// Get token, and renew in case it expired
     ///
     /// # Errors
-    /// If renew fails
-    ///
-    /// # Panics
-    /// Panics if token expires somehow (shouldn't)
+    /// If renew fails or background task stopped
     pub async fn get_token(&self, client: Arc<Client>) -> LoginResult<String> {
         let (tx, rx) = oneshot::channel();
         self.sender
-            .send((client, tx))
+            .send(Message::Get((client, tx)))
             .await
-            .expect("failed to send token request");
-        rx.await.unwrap()
+            .map_err(|_| LoginError::WorkerGone)?;
+        rx.await.map_err(|_| LoginError::WorkerGone)?
     }
 
     // Issue new token from api
     pub async fn login((username, password): (&str, &str), client: &Client) -> LoginResult<String> {
+        client.throttle().await;
+        let permit = client.acquire().await?;
         let res = client
             .reqwest_client()
-            .post(client.url().join("Login").unwrap())
+            .post(client.join("Login").map_err(|_| LoginError::BadUrl)?)
             .body(format!("username={username}&password={password}"))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .send()
             .await?;
+        drop(permit);
+
+        if res.status().is_server_error() {
+            return Err(LoginError::Server(res.status()));
+        }
+        // Wrong credentials re-render login page (200) or are rejected (4xx)
+        if !res.status().is_redirection() {
+            return Err(LoginError::Login(Box::new(res)));
+        }
 
-        if res.status().as_u16() != 302 {
-            return Err(LoginError::Login(res));
+        if let Some(token) = Self::find_token(&res) {
+            return Ok(token);
         }
 
-        let v = res
+        // Some schools set the cookie only after an intermediate redirect, follow it once
+        let location = res
             .headers()
+            .get("Location")
+            .and_then(|l| l.to_str().ok())
+            .ok_or(LoginError::Redirect)?;
+        let url = client
+            .url()
+            .join(location)
+            .map_err(|_| LoginError::Redirect)?;
+        let cookies = Self::cookies(&res)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        client.throttle().await;
+        let _permit = client.acquire().await?;
+        let res = client
+            .reqwest_client()
+            .get(url)
+            .header("Cookie", cookies)
+            .send()
+            .await?;
+
+        Self::find_token(&res).ok_or(LoginError::CookieParse)
+    }
+
+    /// Issue new token pair from API login endpoint
+    ///
+    /// # Errors
+    /// If login fails or response isn't valid token
+    pub async fn login_api(
+        (username, password): (&str, &str),
+        client: &Client,
+    ) -> LoginResult<ApiToken> {
+        Self::api_token(
+            client,
+            &[
+                ("client_id", "ANDR"),
+                ("grant_type", "password"),
+                ("username", username),
+                ("password", password),
+            ],
+        )
+        .await
+    }
+
+    /// Renew token pair using refresh token
+    ///
+    /// # Errors
+    /// If renew fails or response isn't valid token
+    pub async fn refresh_api(refresh_token: &str, client: &Client) -> LoginResult<ApiToken> {
+        Self::api_token(
+            client,
+            &[
+                ("client_id", "ANDR"),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ],
+        )
+        .await
+    }
+
+    /// Post form to API token endpoint
+    ///
+    /// Returns [`LoginError::ApiUnavailable`] if school doesn't expose the endpoint.
+    async fn api_token(client: &Client, form: &[(&str, &str)]) -> LoginResult<ApiToken> {
+        client.throttle().await;
+        let permit = client.acquire().await?;
+        let res = client
+            .reqwest_client()
+            .post(client.join("api/login").map_err(|_| LoginError::BadUrl)?)
+            .form(form)
+            .send()
+            .await?;
+        drop(permit);
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Err(LoginError::ApiUnavailable);
+        }
+        if res.status().is_server_error() {
+            return Err(LoginError::Server(res.status()));
+        }
+        if !res.status().is_success() {
+            return Err(LoginError::Login(Box::new(res)));
+        }
+        Self::parse_api_token(&res.text().await?)
+    }
+
+    /// Parse token pair from JSON response of API token endpoint
+    fn parse_api_token(body: &str) -> LoginResult<ApiToken> {
+        serde_json::from_str(body).map_err(|_| LoginError::TokenParse)
+    }
+
+    /// Get name and value of cookies set by response
+    fn cookies(res: &Response) -> impl Iterator<Item = (&str, &str)> {
+        res.headers()
             .get_all("Set-Cookie")
             .iter()
             .filter_map(|h| h.to_str().ok())
             .filter_map(|h| h.split_once(';'))
             .map(|h| h.0)
             .filter_map(|h| h.split_once('='))
+    }
+
+    /// Get token from cookies set by response
+    fn find_token(res: &Response) -> Option<String> {
+        Self::cookies(res)
             .find(|h| h.0 == "BakaAuth")
-            .map(|h| h.1)
-            .ok_or(LoginError::CookieParse)?;
+            .map(|h| h.1.to_owned())
+    }
+}
 
-        Ok(v.to_owned())
+#[cfg(test)]
+mod tests {
+    use super::{ApiToken, Credentials, LoginError};
+
+    #[test]
+    fn api_token() {
+        let token = Credentials::parse_api_token(
+            r#"{"bak:ApiVersion":"3.13.0","bak:UserId":"XYZ","access_token":"access","token_type":"Bearer","expires_in":3599,"scope":"openid profile bakalari_api","refresh_token":"refresh","id_token":null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            token,
+            ApiToken {
+                access_token: "access".to_owned(),
+                refresh_token: "refresh".to_owned(),
+                expires_in: Some(3599),
+            }
+        );
+    }
+
+    #[test]
+    fn api_token_without_lifetime() {
+        let token =
+            Credentials::parse_api_token(r#"{"access_token":"a","refresh_token":"r"}"#).unwrap();
+        assert_eq!(token.expires_in, None);
+    }
+
+    #[test]
+    fn api_token_error() {
+        for body in [
+            r#"{"error":"invalid_grant","error_description":"Špatný login nebo heslo"}"#,
+            r#"{"access_token":"a"}"#,
+            "<html>not json</html>",
+        ] {
+            assert!(matches!(
+                Credentials::parse_api_token(body),
+                Err(LoginError::TokenParse)
+            ));
+        }
     }
 }
@@ -1,20 +1,48 @@
-use self::util::get_info;
-use super::auth::{Auth, Credentials, LoginError, LoginResult};
-use super::timetable::ParseError as TimetableParseError;
-use reqwest::{redirect::Policy, Client as ReqwestClient, Url};
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use self::util::{get_info, Info};
+use super::auth::{Auth, AuthInput, Credentials, LoginError};
+use super::timetable::{Hour, ParseError as TimetableParseError};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, COOKIE},
+    redirect::Policy,
+    Client as ReqwestClient, Url,
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 use thiserror::Error;
+use tokio::{
+    sync::{AcquireError, Mutex, OnceCell, Semaphore, SemaphorePermit},
+    time::Instant,
+};
 
+use cache::TimetableCache;
+use timetable::ValidatorCache;
+
+pub use cache::PERMANENT_CACHE_TTL;
+pub use config::{ClientConfig, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+pub use other::{AccessMode, SelfCheckReport};
+pub use timetable::FetchedTimetable;
+pub use transport::{Transport, TransportFuture, TransportResponse};
+
+mod cache;
+mod config;
 mod info;
 mod other;
 mod timetable;
+mod transport;
 mod util;
 
 /// Struct that holds HTTP Client and base url
 #[derive(Debug)]
 pub struct Client {
     reqwest_client: ReqwestClient,
+    transport: Box<dyn Transport>,
     url: Url,
+    min_interval: Option<Duration>,
+    next_request: Mutex<Option<Instant>>,
+    accept_language: Option<HeaderValue>,
+    semaphore: Option<Arc<Semaphore>>,
+    config: ClientConfig,
+    /// Last timetables with their validators, for conditional requests
+    timetables: Mutex<ValidatorCache>,
 }
 
 impl Client {
@@ -28,30 +56,183 @@ impl Client {
         &self.url
     }
 
-    /// Create new Bakalari Client
+    /// Create new Bakalari Client with default configuration
     ///
     /// # Panics
     /// This method fails if a TLS backend cannot be initialized, or the resolver cannot load the system configuration.
     #[must_use]
     pub fn new(url: Url) -> Self {
+        Self::with_config(url, ClientConfig::default())
+    }
+
+    /// Create new Bakalari Client with configuration
+    ///
+    /// # Panics
+    /// This method fails if a TLS backend cannot be initialized, or the resolver cannot load the system configuration,
+    /// or if user agent isn't valid header value.
+    #[must_use]
+    pub fn with_config(url: Url, config: ClientConfig) -> Self {
+        let mut builder = ReqwestClient::builder()
+            .redirect(Policy::none())
+            .timeout(config.timeout);
+        if let Some(user_agent) = &config.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        let reqwest_client = builder.build().unwrap();
         Self {
-            reqwest_client: ReqwestClient::builder()
-                .redirect(Policy::none())
-                .build()
-                .unwrap(),
+            transport: Box::new(reqwest_client.clone()),
+            reqwest_client,
             url,
+            min_interval: None,
+            next_request: Mutex::new(None),
+            accept_language: Some(HeaderValue::from_static("cs")),
+            semaphore: None,
+            config,
+            timetables: Mutex::new(ValidatorCache::default()),
+        }
+    }
+
+    /// Set Accept-Language header sent with requests (`cs` by default)
+    ///
+    /// Parsing of weekday names in permanent timetables expects Czech or English names.
+    #[must_use]
+    pub fn accept_language(mut self, language: Option<HeaderValue>) -> Self {
+        self.accept_language = language;
+        self
+    }
+
+    /// Set minimum interval between requests (off by default)
+    ///
+    /// Requests sent sooner wait until the interval has elapsed,
+    /// so that bursts don't hammer the school server.
+    #[must_use]
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = Some(interval);
+        self
+    }
+
+    /// Wait until next request is allowed by minimum interval
+    pub async fn throttle(&self) {
+        let Some(interval) = self.min_interval else {
+            return;
+        };
+        let mut next_request = self.next_request.lock().await;
+        if let Some(next) = *next_request {
+            tokio::time::sleep_until(next).await;
+        }
+        *next_request = Some(Instant::now() + interval);
+    }
+
+    /// Limit number of simultaneous requests (unlimited by default)
+    ///
+    /// Semaphore can be shared between multiple clients to limit them together.
+    /// Once it's closed, requests fail with [`RequestError::LimitClosed`].
+    #[must_use]
+    pub fn concurrency_limit(mut self, semaphore: Arc<Semaphore>) -> Self {
+        self.semaphore = Some(semaphore);
+        self
+    }
+
+    /// Acquire permit to send request, `None` if unlimited
+    ///
+    /// # Errors
+    /// If semaphore was closed, request must not be sent unlimited then
+    pub async fn acquire(&self) -> Result<Option<SemaphorePermit<'_>>, AcquireError> {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.acquire().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Create new Bakalari Client that sends scraping requests through custom transport
+    ///
+    /// # Panics
+    /// This method fails if a TLS backend cannot be initialized, or the resolver cannot load the system configuration.
+    #[must_use]
+    pub fn with_transport(url: Url, transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Box::new(transport),
+            ..Self::new(url)
+        }
+    }
+
+    /// Join path to base url
+    ///
+    /// # Errors
+    /// If resulting url is invalid
+    pub fn join(&self, path: &str) -> RequestResult<Url> {
+        self.url.join(path).map_err(|_| RequestError::BadUrl)
+    }
+
+    /// Send GET request to path relative to base url, with optional Cookie header
+    ///
+    /// # Errors
+    /// If url is invalid or request fails
+    pub async fn get(&self, path: &str, cookie: Option<&str>) -> RequestResult<TransportResponse> {
+        self.get_with_headers(path, cookie, HeaderMap::new()).await
+    }
+
+    /// Send GET request to path relative to base url, with optional Cookie header and extra headers
+    ///
+    /// Transient errors are retried as configured by [`ClientConfig`].
+    ///
+    /// # Errors
+    /// If url is invalid or request fails
+    pub async fn get_with_headers(
+        &self,
+        path: &str,
+        cookie: Option<&str>,
+        mut headers: HeaderMap,
+    ) -> RequestResult<TransportResponse> {
+        let url = self.join(path)?;
+        if let Some(cookie) = cookie {
+            let cookie = HeaderValue::from_str(cookie).map_err(|_| RequestError::InvalidToken)?;
+            headers.insert(COOKIE, cookie);
+        }
+        if let Some(language) = &self.accept_language {
+            headers.insert(ACCEPT_LANGUAGE, language.clone());
+        }
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+            let permit = self.acquire().await?;
+            let result = self.transport.get(url.clone(), headers.clone()).await;
+            drop(permit);
+
+            let transient = match &result {
+                Ok(res) => res.status.is_server_error(),
+                Err(RequestError::Request(err)) => err.is_timeout() || err.is_connect(),
+                Err(_) => false,
+            };
+            if !transient || attempt >= self.config.retries {
+                return result;
+            }
+            tokio::time::sleep(self.config.backoff(attempt)).await;
+            attempt += 1;
         }
     }
 }
 
 /// Bakalari api struct
+///
+/// Cloning is cheap, clones share the client, token and lists of objects
+/// (so it can be used directly as shared state).
 #[derive(Debug, Clone)]
 pub struct Bakalari {
     client: Arc<Client>,
     auth: Auth,
-    classes: HashMap<String, String>,
-    teachers: HashMap<String, String>,
-    rooms: HashMap<String, String>,
+    classes: Arc<HashMap<String, String>>,
+    /// Names of classes in order of Bakalari's dropdown
+    class_order: Arc<Vec<String>>,
+    teachers: Arc<HashMap<String, String>>,
+    rooms: Arc<HashMap<String, String>>,
+    school_name: Option<String>,
+    /// Bell schedule, fetched once
+    bell_schedule: Arc<OnceCell<Vec<Hour>>>,
+    /// Whether timetables reuse fetched bell schedule instead of parsing hours
+    reuse_bell_schedule: bool,
+    /// Parsed timetables, if caching is enabled
+    cache: Option<Arc<TimetableCache>>,
 }
 
 impl Bakalari {
@@ -61,26 +242,83 @@ impl Bakalari {
         &self.client
     }
 
+    /// Create Bakalari instance with any authentication
+    ///
+    /// # Errors
+    /// Returns error if authentication fails
+    pub async fn connect(url: Url, auth: AuthInput) -> Result<Self, RequestError> {
+        Self::connect_with_client(Client::new(url), auth).await
+    }
+
+    /// Create Bakalari instance with any authentication, using configured client
+    ///
+    /// # Errors
+    /// Returns error if authentication fails
+    pub async fn connect_with_client(
+        client: Client,
+        auth: AuthInput,
+    ) -> Result<Self, RequestError> {
+        match auth {
+            AuthInput::Password { username, password } => {
+                Self::from_creds_with_client((username, password), client).await
+            }
+            AuthInput::Token(token) => Self::with_auth(client.into(), Auth::Token(token)).await,
+            AuthInput::SsoCookie(cookie) => {
+                Self::with_auth(client.into(), Auth::Cookie(cookie)).await
+            }
+            AuthInput::None => Self::no_auth_with_client(client).await,
+        }
+    }
+
     /// Create Bakalari instance from username and password
     ///
     /// # Errors
     /// Returns error if authentication fails
     pub async fn from_creds(creds: (String, String), url: Url) -> Result<Self, RequestError> {
-        let client = Arc::new(Client::new(url));
+        Self::from_creds_with_client(creds, Client::new(url)).await
+    }
+
+    /// Create Bakalari instance from username and password, using configured client
+    ///
+    /// # Errors
+    /// Returns error if authentication fails
+    pub async fn from_creds_with_client(
+        creds: (String, String),
+        client: Client,
+    ) -> Result<Self, RequestError> {
+        let client = Arc::new(client);
         let auth = Auth::from_creds((creds.0, creds.1), &client).await?;
-        let (classes, teachers, rooms) = get_info(
-            client.reqwest_client(),
-            client.url(),
-            Some(&auth.get_token(client.clone()).await?),
-        )
-        .await?;
-        Ok(Self {
-            client,
-            auth,
-            classes,
-            teachers,
-            rooms,
-        })
+        Self::with_auth(client, auth).await
+    }
+
+    /// Create Bakalari instance from username and password, renewing token after `lifetime`
+    ///
+    /// # Errors
+    /// Returns error if authentication fails
+    pub async fn from_creds_with_lifetime(
+        creds: (String, String),
+        client: Client,
+        lifetime: Duration,
+    ) -> Result<Self, RequestError> {
+        let client = Arc::new(client);
+        let auth = Auth::from_creds_with_lifetime(creds, &client, lifetime).await?;
+        Self::with_auth(client, auth).await
+    }
+
+    /// Create Bakalari instance from username and password, failing if it takes longer than `total`
+    ///
+    /// Deadline covers both login and fetching list of objects.
+    ///
+    /// # Errors
+    /// Returns error if authentication fails or [`RequestError::Timeout`] on deadline
+    pub async fn from_creds_with_timeout(
+        creds: (String, String),
+        url: Url,
+        total: Duration,
+    ) -> Result<Self, RequestError> {
+        tokio::time::timeout(total, Self::from_creds(creds, url))
+            .await
+            .map_err(|_| RequestError::Timeout)?
     }
 
     /// Create Bakalari instance without storing credentials
@@ -88,18 +326,20 @@ impl Bakalari {
     /// # Errors
     /// Returns error if authentication fails
     pub async fn from_creds_no_store(creds: (&str, &str), url: Url) -> Result<Self, RequestError> {
-        let client = Arc::new(Client::new(url));
+        Self::from_creds_no_store_with_client(creds, Client::new(url)).await
+    }
+
+    /// Create Bakalari instance without storing credentials, using configured client
+    ///
+    /// # Errors
+    /// Returns error if authentication fails
+    pub async fn from_creds_no_store_with_client(
+        creds: (&str, &str),
+        client: Client,
+    ) -> Result<Self, RequestError> {
+        let client = Arc::new(client);
         let token = Credentials::login((creds.0, creds.1), &client).await?;
-        let (classes, teachers, rooms) =
-            get_info(client.reqwest_client(), client.url(), Some(&token)).await?;
-        let auth = Auth::from_token(token);
-        Ok(Self {
-            client,
-            auth,
-            classes,
-            teachers,
-            rooms,
-        })
+        Self::with_auth(client, Auth::from_token(token)).await
     }
 
     /// Create Bakalari instance without authentication
@@ -107,27 +347,107 @@ impl Bakalari {
     /// # Errors
     /// Returns error if authentication fails
     pub async fn no_auth(url: Url) -> Result<Self, RequestError> {
-        let client = Arc::new(Client::new(url));
-        let (classes, teachers, rooms) =
-            get_info(client.reqwest_client(), client.url(), None).await?;
-        Ok(Self {
-            client,
-            auth: Auth::None,
+        Self::no_auth_with_client(Client::new(url)).await
+    }
+
+    /// Create Bakalari instance without authentication, sending requests through custom transport
+    ///
+    /// # Errors
+    /// Returns error if request fails
+    pub async fn no_auth_with_transport(
+        url: Url,
+        transport: impl Transport + 'static,
+    ) -> Result<Self, RequestError> {
+        Self::no_auth_with_client(Client::with_transport(url, transport)).await
+    }
+
+    /// Create Bakalari instance without authentication, using configured client
+    ///
+    /// # Errors
+    /// Returns error if request fails
+    pub async fn no_auth_with_client(client: Client) -> Result<Self, RequestError> {
+        Self::with_auth(client.into(), Auth::None).await
+    }
+
+    /// Create Bakalari instance with authentication, fetching list of objects
+    async fn with_auth(client: Arc<Client>, auth: Auth) -> Result<Self, RequestError> {
+        let headers = auth_headers(&auth, client.clone()).await?;
+        let Info {
             classes,
             teachers,
             rooms,
+            school_name,
+        } = get_info(&client, headers).await?;
+        let class_order = classes.iter().map(|(name, _)| name.to_owned()).collect();
+        Ok(Self {
+            client,
+            auth,
+            classes: Arc::new(classes.into_iter().collect()),
+            class_order: Arc::new(class_order),
+            teachers: Arc::new(teachers.into_iter().collect()),
+            rooms: Arc::new(rooms.into_iter().collect()),
+            school_name,
+            bell_schedule: Arc::new(OnceCell::new()),
+            reuse_bell_schedule: false,
+            cache: None,
         })
     }
 
-    /// Get token
+    /// Reuse bell schedule (once fetched by [`Bakalari::bell_schedule`])
+    /// instead of parsing hours of every timetable
+    #[must_use]
+    pub const fn reuse_bell_schedule(mut self, reuse: bool) -> Self {
+        self.reuse_bell_schedule = reuse;
+        self
+    }
+
+    /// Cache timetables returned by [`Bakalari::get_timetable`] for `ttl`
+    ///
+    /// Permanent timetables are kept for at least [`PERMANENT_CACHE_TTL`].
+    /// Cache is shared by clones made after this call.
+    #[must_use]
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        self.cache = Some(Arc::new(TimetableCache::new(ttl)));
+        self
+    }
+
+    /// Get lifetime of token, `None` if credentials aren't stored
+    #[must_use]
+    pub const fn token_lifetime(&self) -> Option<Duration> {
+        self.auth.token_lifetime()
+    }
+
+    /// Fetch or renew token ahead of requests (no-op without authentication)
     ///
     /// # Errors
     /// If renew fails
-    async fn get_token(&self) -> LoginResult<Cow<'_, String>> {
-        self.auth.get_token(self.client.clone()).await
+    pub async fn ensure_token(&self) -> RequestResult<()> {
+        self.auth_headers().await?;
+        Ok(())
+    }
+
+    /// Get Cookie header for requests, empty without authentication
+    ///
+    /// # Errors
+    /// If renew fails or token isn't valid header value
+    async fn auth_headers(&self) -> RequestResult<HeaderMap> {
+        auth_headers(&self.auth, self.client.clone()).await
     }
 }
 
+/// Get Cookie header for requests, empty without authentication
+///
+/// # Errors
+/// If renew fails or token isn't valid header value
+async fn auth_headers(auth: &Auth, client: Arc<Client>) -> RequestResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    if let Some(cookie) = auth.get_cookie(client).await? {
+        let cookie = HeaderValue::from_str(&cookie).map_err(|_| RequestError::InvalidToken)?;
+        headers.insert(COOKIE, cookie);
+    }
+    Ok(headers)
+}
+
 /// Error of request to api
 #[derive(Debug, Error)]
 pub enum RequestError {
@@ -142,6 +462,60 @@ pub enum RequestError {
     ParseFailed(#[from] TimetableParseError),
     #[error("school requires authentication")]
     AuthRequired,
+    #[error("token contains invalid characters")]
+    InvalidToken,
+    #[error("invalid url")]
+    BadUrl,
+    #[error("no classes, teachers or rooms found")]
+    NoObjectsFound,
+    #[error("timed out")]
+    Timeout,
+    /// Task fetching timetable panicked or was cancelled
+    #[error("{0}")]
+    Task(#[from] tokio::task::JoinError),
+    /// Semaphore limiting concurrent requests was closed
+    #[error("concurrency limit closed")]
+    LimitClosed(#[from] AcquireError),
 }
 
 pub type RequestResult<T> = Result<T, RequestError>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Client, RequestError, Transport, TransportFuture, TransportResponse};
+    use reqwest::{header::HeaderMap, StatusCode, Url};
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+
+    /// Transport answering every request with empty page
+    #[derive(Debug)]
+    struct EmptyTransport;
+
+    impl Transport for EmptyTransport {
+        fn get(&self, _url: Url, _headers: HeaderMap) -> TransportFuture<'_> {
+            Box::pin(async {
+                Ok::<_, RequestError>(TransportResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: String::new(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_concurrency_limit() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let url = Url::parse("https://bakalari.example.com/").unwrap();
+        let client =
+            Client::with_transport(url, EmptyTransport).concurrency_limit(semaphore.clone());
+        assert!(client.get("timetable/public", None).await.is_ok());
+
+        // Requests aren't sent without limit once semaphore is closed
+        semaphore.close();
+        assert!(matches!(
+            client.get("timetable/public", None).await,
+            Err(RequestError::LimitClosed(_))
+        ));
+    }
+}
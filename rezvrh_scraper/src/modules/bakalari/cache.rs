@@ -0,0 +1,164 @@
+use super::{RequestError, RequestResult};
+use crate::modules::timetable::{Timetable, Type, Which};
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+use tokio::{
+    sync::{Mutex, OnceCell},
+    time::Instant,
+};
+
+/// Minimal lifetime of cached permanent timetables, they rarely change
+pub const PERMANENT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Timetable with time when it was fetched from server
+type Fetched = (Timetable, DateTime<Utc>);
+
+/// Fetched timetable with time when fetch finished, empty while fetching or after failure
+type Entry = Arc<OnceCell<(Fetched, Instant)>>;
+
+/// Parsed timetables kept for limited time
+///
+/// Concurrent requests for the same timetable share one fetch.
+#[derive(Debug)]
+pub struct TimetableCache {
+    ttl: Duration,
+    permanent_ttl: Duration,
+    entries: Mutex<HashMap<(Which, Type), Entry>>,
+}
+
+impl TimetableCache {
+    /// Create empty cache, permanent timetables live at least [`PERMANENT_CACHE_TTL`]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            permanent_ttl: ttl.max(PERMANENT_CACHE_TTL),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get cached timetable with its fetch time, or fetch it if it isn't cached or expired
+    ///
+    /// # Errors
+    /// If fetch fails, failed fetch isn't cached
+    pub async fn get_or_fetch<F>(
+        &self,
+        which: Which,
+        timetable_type: &Type,
+        fetch: F,
+    ) -> RequestResult<Fetched>
+    where
+        F: Future<Output = RequestResult<Fetched>>,
+    {
+        let cell = {
+            let mut entries = self.entries.lock().await;
+            // Forget expired timetables and failed fetches nobody waits for,
+            // so that the map doesn't grow forever
+            entries.retain(|(which, _), cell| match cell.get() {
+                Some((_, fetched)) => fetched.elapsed() < self.ttl(*which),
+                None => Arc::strong_count(cell) > 1,
+            });
+            entries
+                .entry((which, timetable_type.clone()))
+                .or_default()
+                .clone()
+        };
+        let (fetched, _) = cell
+            .get_or_try_init(|| async { Ok::<_, RequestError>((fetch.await?, Instant::now())) })
+            .await?;
+        Ok(fetched.clone())
+    }
+
+    /// Lifetime of cached timetable, counted from end of its fetch
+    const fn ttl(&self, which: Which) -> Duration {
+        match which {
+            Which::Permanent => self.permanent_ttl,
+            Which::Actual | Which::Next => self.ttl,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimetableCache;
+    use crate::modules::bakalari::RequestError;
+    use crate::modules::timetable::{Timetable, Type, Which};
+    use chrono::{DateTime, Utc};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    /// Fetch empty timetable after `delay`, counting fetches
+    async fn fetch(
+        count: &AtomicUsize,
+        delay: Duration,
+    ) -> Result<(Timetable, DateTime<Utc>), RequestError> {
+        tokio::time::sleep(delay).await;
+        count.fetch_add(1, Ordering::SeqCst);
+        Ok((Timetable::new(Vec::new(), Vec::new()), Utc::now()))
+    }
+
+    fn class() -> Type {
+        Type::Class("ZA".to_owned())
+    }
+
+    #[tokio::test]
+    async fn lifetime_starts_after_fetch() {
+        let cache = TimetableCache::new(Duration::from_millis(150));
+        let count = AtomicUsize::new(0);
+        // Fetch takes longer than lifetime, but the result is fresh once it finishes
+        cache
+            .get_or_fetch(
+                Which::Actual,
+                &class(),
+                fetch(&count, Duration::from_millis(200)),
+            )
+            .await
+            .unwrap();
+        cache
+            .get_or_fetch(Which::Actual, &class(), fetch(&count, Duration::ZERO))
+            .await
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_evicted() {
+        let cache = TimetableCache::new(Duration::ZERO);
+        let count = AtomicUsize::new(0);
+        for _ in 0..2 {
+            cache
+                .get_or_fetch(Which::Actual, &class(), fetch(&count, Duration::ZERO))
+                .await
+                .unwrap();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // Accessing other timetable forgets the expired one
+        cache
+            .get_or_fetch(Which::Next, &class(), fetch(&count, Duration::ZERO))
+            .await
+            .unwrap();
+        let entries = cache.entries.lock().await;
+        assert_eq!(entries.len(), 1);
+        assert!(entries.contains_key(&(Which::Next, class())));
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_not_cached() {
+        let cache = TimetableCache::new(Duration::from_secs(60));
+        let result = cache
+            .get_or_fetch(Which::Actual, &class(), async {
+                Err(RequestError::AuthRequired)
+            })
+            .await;
+        assert!(matches!(result, Err(RequestError::AuthRequired)));
+
+        let count = AtomicUsize::new(0);
+        cache
+            .get_or_fetch(Which::Actual, &class(), fetch(&count, Duration::ZERO))
+            .await
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
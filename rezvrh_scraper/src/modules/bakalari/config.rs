@@ -0,0 +1,46 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default timeout of whole request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default User-Agent, so that operators can whitelist the scraper
+pub const DEFAULT_USER_AGENT: &str = concat!("rezvrh/", env!("CARGO_PKG_VERSION"));
+
+/// Configuration of HTTP client
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientConfig {
+    /// Timeout of whole request (connect, sending and reading response)
+    pub timeout: Duration,
+    /// How many times scraping request is retried after transient error
+    /// (timeout, connection error or 5xx status)
+    pub retries: u32,
+    /// Delay before first retry, doubled with each next one
+    pub retry_delay: Duration,
+    /// User-Agent header, reqwest doesn't send any when `None`
+    pub user_agent: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            retries: 2,
+            retry_delay: Duration::from_millis(500),
+            user_agent: Some(DEFAULT_USER_AGENT.to_owned()),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Delay before retry number `attempt` (from 0), with up to 50 % jitter
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .retry_delay
+            .saturating_mul(2_u32.saturating_pow(attempt));
+        // Cheap jitter, doesn't need to be random, only spread out clients
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        delay + (delay / 2).mul_f64(f64::from(nanos % 1000) / 1000.0)
+    }
+}
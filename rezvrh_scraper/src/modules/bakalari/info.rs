@@ -1,6 +1,24 @@
 use crate::{modules::timetable::Type, Bakalari, Type as RawType};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// List of objects as `[{"name": ..., "id": ...}]`, sorted by name
+fn objects_list(objects: &HashMap<String, String>) -> Value {
+    let mut objects = objects.iter().collect::<Vec<_>>();
+    objects.sort();
+    objects
+        .into_iter()
+        .map(|(name, id)| json!({ "name": name, "id": id }))
+        .collect()
+}
 
 impl Bakalari {
+    /// Get name of school (from title of timetable page)
+    #[must_use]
+    pub fn school_name(&self) -> Option<&str> {
+        self.school_name.as_deref()
+    }
+
     /// Get list of classes
     #[must_use]
     pub fn get_classes(&self) -> Vec<String> {
@@ -10,6 +28,12 @@ impl Bakalari {
             .collect()
     }
 
+    /// Get list of classes in order of Bakalari's dropdown (not alphabetized)
+    #[must_use]
+    pub fn classes_ordered(&self) -> &[String] {
+        &self.class_order
+    }
+
     /// Get class selector
     #[must_use]
     pub fn get_class(&self, class: &str) -> Option<Type> {
@@ -69,4 +93,25 @@ impl Bakalari {
             RawType::Room => self.get_room(name),
         }
     }
+
+    /// Get all objects as JSON `{"classes": [...], "teachers": [...], "rooms": [...]}`
+    ///
+    /// Objects have `name` and `id`, classes are in order of Bakalari's dropdown,
+    /// teachers and rooms are sorted by name.
+    #[must_use]
+    pub fn objects_json(&self) -> Value {
+        let classes = self
+            .class_order
+            .iter()
+            .filter_map(|name| {
+                let id = self.classes.get(name)?;
+                Some(json!({ "name": name, "id": id }))
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "classes": classes,
+            "teachers": objects_list(&self.teachers),
+            "rooms": objects_list(&self.rooms),
+        })
+    }
 }
@@ -1,23 +1,74 @@
-use super::{Bakalari, RequestError, RequestResult};
+use super::{
+    util::{check_redirect, get_info},
+    Bakalari, Client, RequestError, RequestResult,
+};
+use crate::modules::{
+    auth::Auth,
+    timetable::{Type, Which},
+};
+use reqwest::Url;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Whether school's timetables can be viewed without login
+#[derive(Debug, Serialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessMode {
+    /// Timetables are public
+    Public,
+    /// Login is required
+    AuthRequired,
+}
+
+/// Result of [`Bakalari::self_check`], which steps passed
+#[derive(Debug, Serialize, PartialEq, Eq, Hash, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfCheckReport {
+    /// Authentication is accepted
+    pub login: bool,
+    /// Lists of classes, teachers and rooms are present
+    pub objects: bool,
+    /// Permanent class timetable was parsed
+    pub permanent: bool,
+    /// Actual class timetable was parsed
+    pub actual: bool,
+    /// Next week class timetable was parsed
+    pub next: bool,
+}
+
+impl SelfCheckReport {
+    /// Whether all steps passed
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        self.login && self.objects && self.permanent && self.actual && self.next
+    }
+}
 
 impl Bakalari {
+    /// Find out whether school requires login, by requesting timetables without credentials
+    ///
+    /// # Errors
+    /// Returns error if request fails or server responds unexpectedly
+    pub async fn probe(url: Url) -> RequestResult<AccessMode> {
+        let response = Client::new(url).get("timetable/public", None).await?;
+        match check_redirect(&response) {
+            Ok(()) => Ok(AccessMode::Public),
+            Err(RequestError::AuthRequired) => Ok(AccessMode::AuthRequired),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Test if connection is working
     ///
     /// # Errors
     /// Returns error if request fails
-    ///
-    /// # Panics
-    /// If url join fails (shouldn't)
     pub async fn test(&self) -> RequestResult<()> {
         let client = self.client();
         let res = client
-            .reqwest_client()
-            .get(client.url().join("timetable/public").unwrap())
-            .header("Cookie", format!("BakaAuth={}", self.get_token().await?))
-            .send()
+            .get_with_headers("timetable/public", None, self.auth_headers().await?)
             .await?;
 
-        let text = res.text().await?;
+        let text = res.body;
 
         if !text.contains("timetable") {
             return Err(RequestError::UnknownResponse("timetable not present"));
@@ -25,4 +76,75 @@ impl Bakalari {
 
         Ok(())
     }
+
+    /// Measure round-trip time of request to public timetable (response isn't parsed)
+    ///
+    /// # Errors
+    /// Returns error if request fails
+    pub async fn ping(&self) -> RequestResult<Duration> {
+        let headers = self.auth_headers().await?;
+        let start = Instant::now();
+        self.client()
+            .get_with_headers("timetable/public", None, headers)
+            .await?;
+        Ok(start.elapsed())
+    }
+
+    /// Check whether authentication is still accepted by server
+    ///
+    /// Returns `false` if server redirects to login, always `true` without authentication.
+    ///
+    /// # Errors
+    /// Returns error if request fails
+    pub async fn check_auth(&self) -> RequestResult<bool> {
+        if let Auth::None = self.auth {
+            return Ok(true);
+        }
+
+        let res = self
+            .client()
+            .get_with_headers("timetable/public", None, self.auth_headers().await?)
+            .await?;
+
+        let login_redirect = res.status.is_redirection()
+            && res
+                .headers
+                .get("Location")
+                .and_then(|location| location.to_str().ok())
+                .is_some_and(|location| location.contains("login"));
+
+        Ok(!login_redirect)
+    }
+
+    /// Check login, lists of objects and parsing of one class timetable of each kind
+    ///
+    /// Unlike [`Bakalari::test`], failures don't stop the check, report says which steps failed.
+    pub async fn self_check(&self) -> SelfCheckReport {
+        let mut report = SelfCheckReport {
+            login: self.check_auth().await.unwrap_or(false),
+            ..SelfCheckReport::default()
+        };
+
+        let Ok(headers) = self.auth_headers().await else {
+            return report;
+        };
+        let Ok(info) = get_info(self.client(), headers).await else {
+            return report;
+        };
+        report.objects = true;
+
+        let Some((_, class)) = info.classes.first() else {
+            return report;
+        };
+        let class = Type::Class(class.to_owned());
+        for (which, passed) in [
+            (Which::Permanent, &mut report.permanent),
+            (Which::Actual, &mut report.actual),
+            (Which::Next, &mut report.next),
+        ] {
+            *passed = self.get_timetable(which, &class).await.is_ok();
+        }
+
+        report
+    }
 }
@@ -1,34 +1,623 @@
-use super::{Bakalari, RequestResult};
-use crate::modules::timetable::{Timetable, Type, Which};
+use super::{util::check_redirect, Bakalari, RequestError, RequestResult};
+use crate::modules::timetable::{Day, Hour, Lesson, RawType, Timetable, Type, Which};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode, Url,
+};
+use std::{collections::HashMap, time::Duration};
+use tokio::{task::JoinSet, time::Instant};
+
+/// Timetable together with its source
+#[derive(Debug, Clone)]
+pub struct FetchedTimetable {
+    pub timetable: Timetable,
+    /// Source html (only if requested)
+    pub html: Option<String>,
+    pub url: Url,
+    /// When timetable was fetched (of original response if server replied not modified)
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// How many timetables are kept for conditional requests
+const VALIDATOR_CACHE_CAPACITY: usize = 512;
+
+/// How long timetable is kept for conditional requests
+const VALIDATOR_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Timetable kept for conditional requests
+#[derive(Debug, Clone)]
+struct CachedTimetable {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    /// Source html, only if it was requested
+    html: Option<String>,
+    timetable: Timetable,
+    fetched_at: DateTime<Utc>,
+    stored: Instant,
+}
+
+/// Last timetables with their validators, by url
+///
+/// Entries expire after a while and the oldest one is evicted when cache is full,
+/// so that client iterating over all objects doesn't grow without bound.
+#[derive(Debug)]
+pub struct ValidatorCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<Url, CachedTimetable>,
+}
+
+impl Default for ValidatorCache {
+    fn default() -> Self {
+        Self::new(VALIDATOR_CACHE_CAPACITY, VALIDATOR_CACHE_TTL)
+    }
+}
+
+impl ValidatorCache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Get timetable that hasn't expired yet
+    fn get(&mut self, url: &Url) -> Option<CachedTimetable> {
+        self.evict_expired();
+        self.entries.get(url).cloned()
+    }
+
+    /// Store timetable, evicting expired entries and the oldest one if full
+    fn insert(&mut self, url: Url, timetable: CachedTimetable) {
+        self.evict_expired();
+        if !self.entries.contains_key(&url) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.stored)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        if self.capacity > 0 {
+            self.entries.insert(url, timetable);
+        }
+    }
+
+    fn remove(&mut self, url: &Url) {
+        self.entries.remove(url);
+    }
+
+    fn evict_expired(&mut self) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|_, cached| cached.stored.elapsed() < ttl);
+    }
+}
 
 impl Bakalari {
+    /// Get url of public timetable page on Bakalari (the one that is scraped)
+    ///
+    /// # Errors
+    /// If url is invalid
+    pub fn web_url(&self, which: Which, timetable_type: &Type) -> RequestResult<Url> {
+        self.client()
+            .join(&format!("timetable/public/{which}/{timetable_type}"))
+    }
+
     /// Get specific timetable
     ///
+    /// Served from cache if enabled by [`Bakalari::with_cache`], concurrent requests
+    /// for the same timetable then share one fetch.
+    ///
     /// # Errors
     /// If request fails
-    ///
-    /// # Panics
-    /// If url join fails (shouldn't)
     pub async fn get_timetable(
         &self,
         which: Which,
         timetable_type: &Type,
     ) -> RequestResult<Timetable> {
+        let (timetable, _) = self.get_timetable_fetched(which, timetable_type).await?;
+        Ok(timetable)
+    }
+
+    /// Get specific timetable along with time when it was fetched from server
+    ///
+    /// Same as [`Bakalari::get_timetable`], cached timetables keep their original fetch time.
+    ///
+    /// # Errors
+    /// If request fails
+    pub async fn get_timetable_fetched(
+        &self,
+        which: Which,
+        timetable_type: &Type,
+    ) -> RequestResult<(Timetable, DateTime<Utc>)> {
+        let fetch = async {
+            let fetched = self
+                .get_timetable_verbose(which, timetable_type, false)
+                .await?;
+            Ok::<_, RequestError>((fetched.timetable, fetched.fetched_at))
+        };
+        match &self.cache {
+            Some(cache) => cache.get_or_fetch(which, timetable_type, fetch).await,
+            None => fetch.await,
+        }
+    }
+
+    /// Get specific timetable along with its url and optionally source html
+    ///
+    /// Request is conditional if the timetable was fetched recently, source html is only
+    /// kept for that when it was requested.
+    ///
+    /// # Errors
+    /// If request fails
+    pub async fn get_timetable_verbose(
+        &self,
+        which: Which,
+        timetable_type: &Type,
+        keep_html: bool,
+    ) -> RequestResult<FetchedTimetable> {
         let client = self.client();
-        let res = client
-            .reqwest_client()
-            .get(
-                client
-                    .url()
-                    .join(&format!("timetable/public/{which}/{timetable_type}"))
-                    .unwrap(),
-            )
-            .header("Cookie", format!("BakaAuth={}", self.get_token().await?))
-            .send()
+        let path = format!("timetable/public/{which}/{timetable_type}");
+        let url = self.web_url(which, timetable_type)?;
+
+        // Ask server whether timetable changed since last time, unless html is needed
+        // and it wasn't kept
+        let cached = client
+            .timetables
+            .lock()
+            .await
+            .get(&url)
+            .filter(|cached| !keep_html || cached.html.is_some());
+        let mut headers = self.auth_headers().await?;
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        let res = client.get_with_headers(&path, None, headers).await?;
+
+        if res.status == StatusCode::NOT_MODIFIED {
+            let cached = cached.ok_or(RequestError::UnknownResponse(
+                "not modified without cached timetable",
+            ))?;
+            return Ok(FetchedTimetable {
+                timetable: cached.timetable,
+                html: cached.html.filter(|_| keep_html),
+                url,
+                fetched_at: cached.fetched_at,
+            });
+        }
+
+        // Expired or revoked cookie redirects to login
+        check_redirect(&res)?;
+
+        let fetched_at = Utc::now();
+        let html = res.body;
+        let hours = self
+            .bell_schedule
+            .get()
+            .filter(|_| self.reuse_bell_schedule)
+            .cloned();
+        let timetable = Timetable::parse_with_hours(&html, timetable_type, hours)?;
+
+        // Servers ignoring conditional requests don't send validators
+        let etag = res.headers.get(ETAG).cloned();
+        let last_modified = res.headers.get(LAST_MODIFIED).cloned();
+        let mut timetables = client.timetables.lock().await;
+        if etag.is_some() || last_modified.is_some() {
+            timetables.insert(
+                url.clone(),
+                CachedTimetable {
+                    etag,
+                    last_modified,
+                    html: keep_html.then(|| html.clone()),
+                    timetable: timetable.clone(),
+                    fetched_at,
+                    stored: Instant::now(),
+                },
+            );
+        } else {
+            timetables.remove(&url);
+        }
+        drop(timetables);
+
+        Ok(FetchedTimetable {
+            timetable,
+            html: keep_html.then_some(html),
+            url,
+            fetched_at,
+        })
+    }
+
+    /// Find room where teacher has lesson at given time
+    ///
+    /// Returns `None` if teacher has free period, day is over or lesson has no room.
+    ///
+    /// # Errors
+    /// If request or parsing fails
+    pub async fn locate_teacher(
+        &self,
+        teacher: &Type,
+        now: NaiveDateTime,
+    ) -> RequestResult<Option<String>> {
+        let timetable = self.get_timetable(Which::Actual, teacher).await?;
+        let room = timetable.lessons_at(now).and_then(|(_, lessons)| {
+            lessons.iter().find_map(|lesson| match lesson {
+                Lesson::Regular { room, .. } | Lesson::Substitution { room, .. } => room.clone(),
+                Lesson::Canceled { .. } | Lesson::Absent { .. } => None,
+            })
+        });
+        Ok(room)
+    }
+
+    /// Get day of actual timetable with given date
+    ///
+    /// Returns `None` if the day isn't in timetable (e.g. weekend).
+    ///
+    /// # Errors
+    /// If request or parsing fails
+    pub async fn get_today(
+        &self,
+        timetable_type: &Type,
+        now: NaiveDate,
+    ) -> RequestResult<Option<Day>> {
+        let timetable = self.get_timetable(Which::Actual, timetable_type).await?;
+        Ok(timetable.days.into_iter().find(|day| day.date == Some(now)))
+    }
+
+    /// Get bell schedule (hours) of school, fetched once and cached
+    ///
+    /// Hours are the same for all timetables, they are taken from permanent timetable of first object.
+    ///
+    /// # Errors
+    /// If request or parsing fails, or school has no objects
+    pub async fn bell_schedule(&self) -> RequestResult<Vec<Hour>> {
+        let hours = self
+            .bell_schedule
+            .get_or_try_init(|| async {
+                let timetable_type = self
+                    .class_order
+                    .first()
+                    .and_then(|class| self.get_class(class))
+                    .or_else(|| {
+                        self.teachers
+                            .values()
+                            .next()
+                            .map(|id| Type::Teacher(id.to_owned()))
+                    })
+                    .or_else(|| {
+                        self.rooms
+                            .values()
+                            .next()
+                            .map(|id| Type::Room(id.to_owned()))
+                    })
+                    .ok_or(RequestError::NoObjectsFound)?;
+                let res = self
+                    .client()
+                    .get_with_headers(
+                        &format!("timetable/public/{}/{timetable_type}", Which::Permanent),
+                        None,
+                        self.auth_headers().await?,
+                    )
+                    .await?;
+                check_redirect(&res)?;
+                let html = res.body;
+                Ok::<_, RequestError>(Timetable::parse(&html, &timetable_type)?.hours)
+            })
             .await?;
+        Ok(hours.clone())
+    }
 
-        let html = res.text().await?;
+    /// Collect lessons taught by teacher (by abbreviation) from timetables of all classes
+    ///
+    /// At most `limit` class timetables are fetched concurrently. Items are class name,
+    /// day and hour indices and the lesson, in order of classes in Bakalari's dropdown.
+    ///
+    /// # Errors
+    /// If any request or parsing fails
+    pub async fn teacher_lessons_from_classes(
+        &self,
+        teacher_abbr: &str,
+        which: Which,
+        limit: usize,
+    ) -> RequestResult<Vec<(String, usize, usize, Lesson)>> {
+        let timetables = self
+            .get_all_timetables(which, RawType::Class, limit)
+            .await?;
+
+        let lessons = self
+            .class_order
+            .iter()
+            .filter_map(|class| Some((class, timetables.get(class)?)))
+            .flat_map(|(class, timetable)| {
+                timetable
+                    .lessons_with_times()
+                    .filter(|(_, _, lesson)| match lesson {
+                        Lesson::Regular {
+                            teacher_abbr: Some(abbr),
+                            ..
+                        }
+                        | Lesson::Substitution {
+                            teacher_abbr: Some(abbr),
+                            ..
+                        } => abbr == teacher_abbr,
+                        _ => false,
+                    })
+                    .map(|(d, h, lesson)| (class.clone(), d, h, lesson.clone()))
+            })
+            .collect();
+        Ok(lessons)
+    }
+
+    /// Get names of rooms without held lesson in period (by day and hour index)
+    ///
+    /// At most `limit` room timetables are fetched concurrently, names are sorted.
+    ///
+    /// # Errors
+    /// If any request or parsing fails
+    pub async fn get_free_rooms(
+        &self,
+        which: Which,
+        day_index: usize,
+        period: usize,
+        limit: usize,
+    ) -> RequestResult<Vec<String>> {
+        let timetables = self.get_all_timetables(which, RawType::Room, limit).await?;
+        let mut free = timetables
+            .into_iter()
+            .filter(|(_, timetable)| {
+                !timetable
+                    .lesson_at(day_index, period)
+                    .is_some_and(|lessons| lessons.iter().any(Lesson::is_held))
+            })
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        free.sort();
+        Ok(free)
+    }
+
+    /// Get timetables of all objects of type, by name
+    ///
+    /// At most `limit` timetables are fetched concurrently (at least one).
+    ///
+    /// # Errors
+    /// If any request, parsing or fetching task fails, remaining requests are cancelled then
+    pub async fn get_all_timetables(
+        &self,
+        which: Which,
+        typ: RawType,
+        limit: usize,
+    ) -> RequestResult<HashMap<String, Timetable>> {
+        let mut objects = self.get_objects(typ).into_iter();
+        let mut tasks = JoinSet::new();
+        let mut timetables = HashMap::new();
+        loop {
+            while tasks.len() < limit.max(1) {
+                let Some(name) = objects.next() else {
+                    break;
+                };
+                let Some(timetable_type) = self.get_selector(typ, &name) else {
+                    continue;
+                };
+                let bakalari = self.clone();
+                tasks.spawn(async move {
+                    let timetable = bakalari.get_timetable(which, &timetable_type).await;
+                    (name, timetable)
+                });
+            }
+
+            let Some(result) = tasks.join_next().await else {
+                break;
+            };
+            let (name, timetable) = result?;
+            timetables.insert(name, timetable?);
+        }
+        Ok(timetables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachedTimetable, ValidatorCache};
+    use crate::modules::bakalari::{
+        Bakalari, RequestError, Transport, TransportFuture, TransportResponse,
+    };
+    use crate::modules::timetable::Timetable;
+    use crate::modules::timetable::{Lesson, RawType, Which};
+    use chrono::Utc;
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, ETAG, IF_NONE_MATCH, LOCATION},
+        StatusCode, Url,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::time::Instant;
+
+    const PUBLIC: &str = include_str!("../../../fixtures/public.html");
+    const CLASS: &str = include_str!("../../../fixtures/class.html");
+
+    /// Transport serving canned pages by path, with `ETag` support
+    #[derive(Debug, Default)]
+    struct CannedTransport {
+        /// Paths of requests and whether they were conditional
+        requests: Arc<Mutex<Vec<(String, bool)>>>,
+    }
+
+    impl Transport for CannedTransport {
+        fn get(&self, url: Url, headers: HeaderMap) -> TransportFuture<'_> {
+            let conditional =
+                headers.get(IF_NONE_MATCH) == Some(&HeaderValue::from_static("\"1\""));
+            self.requests
+                .lock()
+                .unwrap()
+                .push((url.path().to_owned(), conditional));
+            let (status, body) = match url.path() {
+                "/timetable/public" => (StatusCode::OK, PUBLIC),
+                "/timetable/public/Actual/class/ZA" if conditional => {
+                    (StatusCode::NOT_MODIFIED, "")
+                }
+                "/timetable/public/Actual/class/ZA" => (StatusCode::OK, CLASS),
+                // Session of class 2.B expired
+                "/timetable/public/Actual/class/ZB" => (StatusCode::FOUND, ""),
+                _ => (StatusCode::NOT_FOUND, ""),
+            };
+            let mut headers = HeaderMap::new();
+            headers.insert(ETAG, HeaderValue::from_static("\"1\""));
+            if status == StatusCode::FOUND {
+                headers.insert(LOCATION, HeaderValue::from_static("/login"));
+            }
+            Box::pin(async move {
+                Ok::<_, RequestError>(TransportResponse {
+                    status,
+                    headers,
+                    body: body.to_owned(),
+                })
+            })
+        }
+    }
+
+    async fn bakalari() -> (Bakalari, Arc<Mutex<Vec<(String, bool)>>>) {
+        let transport = CannedTransport::default();
+        let requests = transport.requests.clone();
+        let url = Url::parse("https://bakalari.example.com/").unwrap();
+        let bakalari = Bakalari::no_auth_with_transport(url, transport)
+            .await
+            .unwrap();
+        (bakalari, requests)
+    }
+
+    #[tokio::test]
+    async fn objects_from_public_page() {
+        let (bakalari, _) = bakalari().await;
+        assert_eq!(bakalari.get_objects(RawType::Class), ["1.A", "2.B"]);
+        assert_eq!(bakalari.get_objects(RawType::Teacher), ["Novák Jan"]);
+        assert_eq!(bakalari.get_objects(RawType::Room), ["101"]);
+    }
+
+    #[tokio::test]
+    async fn timetable_through_transport() {
+        let (bakalari, requests) = bakalari().await;
+        let selector = bakalari.get_selector(RawType::Class, "1.A").unwrap();
+
+        let timetable = bakalari
+            .get_timetable(Which::Actual, &selector)
+            .await
+            .unwrap();
+        assert_eq!(timetable.hours.len(), 2);
+        assert_eq!(timetable.days.len(), 3);
+        assert!(matches!(
+            timetable.days[0].lessons[0].as_slice(),
+            [Lesson::Regular { subject, .. }] if subject == "Matematika"
+        ));
+
+        // Second fetch is conditional and served from validators cache
+        let (again, fetched_at) = bakalari
+            .get_timetable_fetched(Which::Actual, &selector)
+            .await
+            .unwrap();
+        assert_eq!(again, timetable);
+        let first = bakalari
+            .get_timetable_verbose(Which::Actual, &selector, false)
+            .await
+            .unwrap();
+        assert_eq!(first.fetched_at, fetched_at);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            *requests,
+            [
+                ("/timetable/public".to_owned(), false),
+                ("/timetable/public/Actual/class/ZA".to_owned(), false),
+                ("/timetable/public/Actual/class/ZA".to_owned(), true),
+                ("/timetable/public/Actual/class/ZA".to_owned(), true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn html_kept_only_on_request() {
+        let (bakalari, requests) = bakalari().await;
+        let selector = bakalari.get_selector(RawType::Class, "1.A").unwrap();
+
+        let first = bakalari
+            .get_timetable_verbose(Which::Actual, &selector, false)
+            .await
+            .unwrap();
+        assert_eq!(first.html, None);
+        // Html wasn't kept, so it has to be fetched again
+        let with_html = bakalari
+            .get_timetable_verbose(Which::Actual, &selector, true)
+            .await
+            .unwrap();
+        assert_eq!(with_html.html.as_deref(), Some(CLASS));
+        let again = bakalari
+            .get_timetable_verbose(Which::Actual, &selector, true)
+            .await
+            .unwrap();
+        assert_eq!(again.html.as_deref(), Some(CLASS));
+
+        let conditional = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .skip(1)
+            .map(|(_, conditional)| *conditional)
+            .collect::<Vec<_>>();
+        assert_eq!(conditional, [false, false, true]);
+    }
+
+    fn cached(stored: Instant) -> CachedTimetable {
+        CachedTimetable {
+            etag: Some(HeaderValue::from_static("\"1\"")),
+            last_modified: None,
+            html: None,
+            timetable: Timetable::new(Vec::new(), Vec::new()),
+            fetched_at: Utc::now(),
+            stored,
+        }
+    }
+
+    #[test]
+    fn validator_cache_evicts_oldest() {
+        let url = |n: u8| Url::parse(&format!("https://bakalari.example.com/{n}")).unwrap();
+        let mut cache = ValidatorCache::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        for n in 0..3 {
+            cache.insert(url(n), cached(now + Duration::from_millis(n.into())));
+        }
+        assert!(cache.get(&url(0)).is_none());
+        assert!(cache.get(&url(1)).is_some());
+        assert!(cache.get(&url(2)).is_some());
+
+        // Replacing entry doesn't evict others
+        cache.insert(url(2), cached(now));
+        assert!(cache.get(&url(1)).is_some());
+    }
+
+    #[test]
+    fn validator_cache_expires() {
+        let url = Url::parse("https://bakalari.example.com/").unwrap();
+        let mut cache = ValidatorCache::new(2, Duration::ZERO);
+        cache.insert(url.clone(), cached(Instant::now()));
+        assert!(cache.get(&url).is_none());
+        assert!(cache.entries.is_empty());
+    }
 
-        Ok(Timetable::parse(&html, timetable_type)?)
+    #[tokio::test]
+    async fn login_redirect_requires_auth() {
+        let (bakalari, _) = bakalari().await;
+        let selector = bakalari.get_selector(RawType::Class, "2.B").unwrap();
+        let result = bakalari.get_timetable(Which::Actual, &selector).await;
+        assert!(matches!(result, Err(RequestError::AuthRequired)));
     }
 }
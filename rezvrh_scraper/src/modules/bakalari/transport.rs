@@ -0,0 +1,146 @@
+use super::{RequestError, RequestResult};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1250};
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE},
+    Client as ReqwestClient, StatusCode, Url,
+};
+use std::{fmt::Debug, future::Future, pin::Pin};
+
+/// Response returned by [`Transport`]
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Future returned by [`Transport::get`]
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = RequestResult<TransportResponse>> + Send + 'a>>;
+
+/// HTTP transport used for scraping requests
+///
+/// Implemented for reqwest client, custom implementations can be used
+/// to feed canned responses into the scraper (e.g. in tests)
+pub trait Transport: Debug + Send + Sync {
+    /// Send GET request with specified headers
+    fn get(&self, url: Url, headers: HeaderMap) -> TransportFuture<'_>;
+}
+
+impl Transport for ReqwestClient {
+    fn get(&self, url: Url, headers: HeaderMap) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let res = ReqwestClient::get(self, url)
+                .headers(headers)
+                .send()
+                .await?;
+            let status = res.status();
+            let headers = res.headers().clone();
+            let body = decode(&headers, &res.bytes().await?);
+            Ok::<_, RequestError>(TransportResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// Decode body using charset from meta tag or Content-Type header
+///
+/// Meta tag wins, as some servers send wrong header. Body that isn't valid UTF-8
+/// even though it should be is decoded as windows-1250 (common on Czech servers).
+fn decode(headers: &HeaderMap, bytes: &[u8]) -> String {
+    let declared = meta_charset(bytes)
+        .or_else(|| {
+            headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(charset_param)
+        })
+        .and_then(|label| Encoding::for_label(label.as_bytes()));
+    let encoding = match declared {
+        Some(encoding) if encoding != UTF_8 => encoding,
+        _ if std::str::from_utf8(bytes).is_err() => WINDOWS_1250,
+        _ => UTF_8,
+    };
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Find charset declared by meta tag in start of document
+fn meta_charset(bytes: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_ascii_lowercase();
+    head.match_indices("<meta").find_map(|(start, _)| {
+        let tag = &head[start..];
+        charset_param(&tag[..tag.find('>')?])
+    })
+}
+
+/// Get value of `charset=` parameter
+fn charset_param(value: &str) -> Option<String> {
+    let value = value.to_ascii_lowercase();
+    let (_, charset) = value.split_once("charset=")?;
+    let charset = charset
+        .trim_start_matches(['"', '\''])
+        .split(|c: char| c == '"' || c == '\'' || c == ';' || c.is_whitespace())
+        .next()?;
+    (!charset.is_empty()).then(|| charset.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{charset_param, decode};
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+    const WITH_META: &[u8] = include_bytes!("../../../fixtures/windows-1250.html");
+    const WITHOUT_META: &[u8] = include_bytes!("../../../fixtures/windows-1250-no-meta.html");
+    const TEXT: &str = "Český jazyk, Dvořák Petr, Žák Řehoř";
+
+    fn content_type(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn meta_charset() {
+        assert!(decode(&HeaderMap::new(), WITH_META).contains(TEXT));
+    }
+
+    #[test]
+    fn meta_wins_over_wrong_header() {
+        let headers = content_type("text/html; charset=utf-8");
+        assert!(decode(&headers, WITH_META).contains(TEXT));
+    }
+
+    #[test]
+    fn header_charset() {
+        let headers = content_type("text/html; charset=\"windows-1250\"");
+        assert!(decode(&headers, WITHOUT_META).contains(TEXT));
+    }
+
+    #[test]
+    fn invalid_utf8_falls_back_to_windows_1250() {
+        assert!(decode(&HeaderMap::new(), WITHOUT_META).contains(TEXT));
+    }
+
+    #[test]
+    fn utf8() {
+        let headers = content_type("text/html; charset=utf-8");
+        assert_eq!(decode(&headers, TEXT.as_bytes()), TEXT);
+    }
+
+    #[test]
+    fn charset_parameter() {
+        assert_eq!(
+            charset_param("text/html; Charset=UTF-8").as_deref(),
+            Some("utf-8")
+        );
+        assert_eq!(
+            charset_param("text/html; charset='windows-1250'; x=y").as_deref(),
+            Some("windows-1250")
+        );
+        assert_eq!(charset_param("text/html"), None);
+        assert_eq!(charset_param("text/html; charset="), None);
+    }
+}
@@ -1,14 +1,13 @@
-use super::RequestError;
+use super::{Client, RequestError, TransportResponse};
 use once_cell::sync::Lazy;
-use reqwest::{Client as ReqwestClient, Url};
+use reqwest::header::HeaderMap;
 use scraper::{Html, Selector};
-use std::collections::HashMap;
 
-/// Extract options for specified selector
+/// Extract options (name and id) for specified selector, in order of the page
 pub fn get_map(
     document: &Html,
     selector: &Selector,
-) -> Result<HashMap<String, String>, RequestError> {
+) -> Result<Vec<(String, String)>, RequestError> {
     document
         .select(selector)
         .map(|e| {
@@ -22,7 +21,7 @@ pub fn get_map(
 
             Ok::<_, RequestError>((name.trim().to_owned(), id.trim().to_owned()))
         })
-        .collect::<Result<HashMap<_, _>, _>>()
+        .collect::<Result<Vec<_>, _>>()
 }
 
 static CLASSES_SELECTOR: Lazy<Selector> =
@@ -31,35 +30,24 @@ static TEACHERS_SELECTOR: Lazy<Selector> =
     Lazy::new(|| Selector::parse("select#selectedTeacher > option[value]").unwrap());
 static ROOMS_SELECTOR: Lazy<Selector> =
     Lazy::new(|| Selector::parse("select#selectedRoom > option[value]").unwrap());
+static TITLE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("head > title").unwrap());
 
-/// Get classes, teachers and rooms
+/// Objects and metadata from timetable page
+pub struct Info {
+    pub classes: Vec<(String, String)>,
+    pub teachers: Vec<(String, String)>,
+    pub rooms: Vec<(String, String)>,
+    pub school_name: Option<String>,
+}
+
+/// Fail if response is redirect, with [`RequestError::AuthRequired`] for redirect to login
 ///
 /// # Errors
-/// If request fails
-pub async fn get_info(
-    client: &ReqwestClient,
-    url: &Url,
-    token: Option<&str>,
-) -> Result<
-    (
-        HashMap<String, String>,
-        HashMap<String, String>,
-        HashMap<String, String>,
-    ),
-    RequestError,
-> {
-    let req = client.get(url.join("timetable/public").unwrap());
-    let req = if let Some(token) = token {
-        req.header("Cookie", format!("BakaAuth={token}"))
-    } else {
-        req
-    };
-
-    let response = req.send().await?;
-
-    if response.status().is_redirection() {
+/// If response is redirect
+pub fn check_redirect(response: &TransportResponse) -> Result<(), RequestError> {
+    if response.status.is_redirection() {
         let location = response
-            .headers()
+            .headers
             .get("Location")
             .ok_or(RequestError::UnknownResponse("missing location header"))?;
         if location
@@ -74,7 +62,21 @@ pub async fn get_info(
         ));
     }
 
-    let text = response.text().await?;
+    Ok(())
+}
+
+/// Get classes, teachers, rooms and school name
+///
+/// # Errors
+/// If request fails
+pub async fn get_info(client: &Client, auth: HeaderMap) -> Result<Info, RequestError> {
+    let response = client
+        .get_with_headers("timetable/public", None, auth)
+        .await?;
+
+    check_redirect(&response)?;
+
+    let text = response.body;
 
     if !text.contains("timetable") {
         return Err(RequestError::UnknownResponse("timetable not present"));
@@ -86,5 +88,21 @@ pub async fn get_info(
     let teachers = get_map(&document, &TEACHERS_SELECTOR)?;
     let rooms = get_map(&document, &ROOMS_SELECTOR)?;
 
-    Ok((classes, teachers, rooms))
+    // Page structure probably changed
+    if classes.is_empty() && teachers.is_empty() && rooms.is_empty() {
+        return Err(RequestError::NoObjectsFound);
+    }
+
+    let school_name = document
+        .select(&TITLE_SELECTOR)
+        .next()
+        .map(|title| title.text().collect::<String>().trim().to_owned())
+        .filter(|title| !title.is_empty());
+
+    Ok(Info {
+        classes,
+        teachers,
+        rooms,
+        school_name,
+    })
 }
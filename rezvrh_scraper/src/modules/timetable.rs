@@ -1,18 +1,36 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, Weekday};
+use day::Layout;
 use day::ParseError as DayParseError;
 use derive_more::Display;
 use hour::ParseError as HourParseError;
 use once_cell::sync::Lazy;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use thiserror::Error;
-use {day::Day, hour::Hour};
 
 mod day;
+mod delta;
+mod diff;
+#[cfg(feature = "fullcalendar")]
+mod fullcalendar;
 mod hour;
+#[cfg(feature = "ical")]
+mod ical;
 mod lesson;
+mod patch;
+mod render;
+mod report;
+mod simplify;
 mod util;
 
-pub use lesson::Lesson;
+pub use day::{Day, Event};
+pub use delta::Delta;
+pub use diff::{diff, LessonChange};
+pub use hour::Hour;
+pub use lesson::{ChangeInfo, Group, Lesson};
+pub use patch::{CellPatch, TimetablePatch};
+pub use report::{DayHours, HoursReport};
 
 /// Which timetable to get
 #[derive(Debug, Display, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
@@ -45,11 +63,21 @@ pub enum Type {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Timetable {
     pub hours: Vec<Hour>,
     pub days: Vec<Day>,
 }
 
+/// How [`Timetable::normalize_periods`] makes days the same length
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Normalize {
+    /// Remove trailing periods that are empty in all days
+    Trim,
+    /// Add empty periods so that every day has a cell for each hour
+    Pad,
+}
+
 #[derive(Debug, Error)]
 pub enum ParseError {
     #[error("failed to parse hour: {0}")]
@@ -58,26 +86,561 @@ pub enum ParseError {
     Day(#[from] DayParseError),
 }
 
-static HOUR_SELECTOR: Lazy<Selector> =
-    Lazy::new(|| Selector::parse("div.bk-hour-wrapper").unwrap());
-static DAY_SELECTOR: Lazy<Selector> =
-    Lazy::new(|| Selector::parse("div.bk-timetable-row").unwrap());
+/// Timetable validation error
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    /// Hour at index doesn't start after previous hour
+    #[error("hour {0} doesn't start after previous hour")]
+    NotIncreasing(usize),
+    /// Hour at index starts before previous hour ends
+    #[error("hour {0} overlaps previous hour")]
+    Overlapping(usize),
+    /// Multiple hours have the same number
+    #[error("hour number {0} is used more than once")]
+    DuplicateNum(usize),
+}
+
+/// Timetables have different hours
+#[derive(Debug, Error)]
+#[error("timetables have different bell schedules")]
+pub struct ScheduleMismatch;
+
+static SELECTED_TEACHER_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("select#selectedTeacher > option[selected]").unwrap());
 
 impl Timetable {
+    /// Create timetable from hours and days (e.g. for tests or synthetic data)
+    #[must_use]
+    pub const fn new(hours: Vec<Hour>, days: Vec<Day>) -> Self {
+        Self { hours, days }
+    }
+
+    /// Parse timetable from html of timetable page
+    ///
+    /// # Errors
+    /// If html doesn't contain valid timetable
+    pub fn from_html(html: &str, timetable_type: &Type) -> Result<Self, ParseError> {
+        Self::parse(html, timetable_type)
+    }
+
     pub(super) fn parse(html: &str, table_type: &Type) -> Result<Self, ParseError> {
+        Self::parse_with_hours(html, table_type, None)
+    }
+
+    /// Parse timetable, reusing already known hours instead of parsing them
+    pub(super) fn parse_with_hours(
+        html: &str,
+        table_type: &Type,
+        hours: Option<Vec<Hour>>,
+    ) -> Result<Self, ParseError> {
         let document = Html::parse_document(html);
+        let layout = Layout::detect(&document);
 
-        let hours = document
-            .select(&HOUR_SELECTOR)
-            .enumerate()
-            .map(|(i, hour)| Hour::parse(hour, i))
-            .collect::<Result<Vec<_>, _>>()?;
+        let hours = match hours {
+            Some(hours) => hours,
+            None => layout.hours(&document)?,
+        };
 
-        let days = document
-            .select(&DAY_SELECTOR)
-            .map(|day| Day::parse(day, table_type))
+        // Teacher timetables don't show the teacher in cells, use the name from the header
+        let teacher_name = if let Type::Teacher(_) = table_type {
+            document
+                .select(&SELECTED_TEACHER_SELECTOR)
+                .next()
+                .and_then(|option| option.text().next())
+                .map(str::trim)
+        } else {
+            None
+        };
+
+        let days = layout
+            .rows(&document)
+            .map(|day| Day::parse(day, layout, table_type, teacher_name))
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self { hours, days })
     }
+
+    /// Get days of timetable
+    #[must_use]
+    pub fn days(&self) -> &[Day] {
+        &self.days
+    }
+
+    /// Get lessons in period by day and hour index, `None` if out of range
+    #[must_use]
+    pub fn lesson_at(&self, day_index: usize, hour_index: usize) -> Option<&[Lesson]> {
+        self.days
+            .get(day_index)?
+            .lessons
+            .get(hour_index)
+            .map(Vec::as_slice)
+    }
+
+    /// Get hour by its number (as shown by Bakalari, not index)
+    #[must_use]
+    pub fn hour(&self, num: usize) -> Option<&Hour> {
+        self.hours.iter().find(|hour| hour.num == num)
+    }
+
+    /// Check that hours are sorted by start, don't overlap and have unique numbers
+    ///
+    /// # Errors
+    /// Returns index (or number) of first offending hour
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut nums = HashSet::new();
+        if let Some(hour) = self.hours.iter().find(|hour| !nums.insert(hour.num)) {
+            return Err(ValidationError::DuplicateNum(hour.num));
+        }
+        for (i, pair) in self.hours.windows(2).enumerate() {
+            let (prev, next) = (&pair[0], &pair[1]);
+            if next.start <= prev.start {
+                return Err(ValidationError::NotIncreasing(i + 1));
+            }
+            if prev.end > next.start {
+                return Err(ValidationError::Overlapping(i + 1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove empty Saturdays and Sundays (weekend rows without lessons or events)
+    ///
+    /// Empty weekdays are kept, as well as weekend days with something on them.
+    pub fn weekdays_only(&mut self) {
+        self.days.retain(|day| {
+            !matches!(day.weekday, Some(Weekday::Sat | Weekday::Sun))
+                || !day.events.is_empty()
+                || day.lessons.iter().any(|lessons| !lessons.is_empty())
+        });
+    }
+
+    /// Make all days have the same number of periods
+    pub fn normalize_periods(&mut self, mode: Normalize) {
+        let len = match mode {
+            Normalize::Trim => self
+                .days
+                .iter()
+                .filter_map(|day| day.lessons.iter().rposition(|lessons| !lessons.is_empty()))
+                .max()
+                .map_or(0, |last| last + 1),
+            Normalize::Pad => self
+                .days
+                .iter()
+                .map(|day| day.lessons.len())
+                .max()
+                .unwrap_or(0)
+                .max(self.hours.len()),
+        };
+        for day in &mut self.days {
+            day.lessons.resize_with(len, Vec::new);
+        }
+    }
+
+    /// Get day by weekday (from date, or from day name on permanent timetables)
+    #[must_use]
+    pub fn day_by_weekday(&self, weekday: Weekday) -> Option<&Day> {
+        self.days.iter().find(|day| day.weekday == Some(weekday))
+    }
+
+    /// Get period index and lessons of period taking place at given time
+    ///
+    /// Day is matched by date, or by weekday on permanent timetables.
+    pub(crate) fn lessons_at(&self, now: NaiveDateTime) -> Option<(usize, &[Lesson])> {
+        let day = self.day_on(now.date())?;
+        let time = now.time();
+        let h = self
+            .hours
+            .iter()
+            .position(|hour| hour.start <= time && time < hour.end)?;
+        day.lessons.get(h).map(|lessons| (h, lessons.as_slice()))
+    }
+
+    /// Get day by date, or by weekday on permanent timetables
+    fn day_on(&self, date: NaiveDate) -> Option<&Day> {
+        self.days.iter().find(|day| match day.date {
+            Some(day_date) => day_date == date,
+            None => day.weekday == Some(date.weekday()),
+        })
+    }
+
+    /// Get period index and lessons of period taking place now
+    ///
+    /// Returns `None` during breaks, free periods and outside school hours.
+    #[must_use]
+    pub fn current_lesson(&self, now: DateTime<Local>) -> Option<(usize, &[Lesson])> {
+        self.lessons_at(now.naive_local())
+            .filter(|(_, lessons)| !lessons.is_empty())
+    }
+
+    /// Get period index and lessons of first period with lessons starting after now
+    ///
+    /// Returns `None` after last lesson of the day.
+    #[must_use]
+    pub fn next_lesson(&self, now: DateTime<Local>) -> Option<(usize, &[Lesson])> {
+        let now = now.naive_local();
+        let day = self.day_on(now.date())?;
+        let time = now.time();
+        self.hours
+            .iter()
+            .enumerate()
+            .filter(|(_, hour)| hour.start > time)
+            .find_map(|(h, _)| {
+                day.lessons
+                    .get(h)
+                    .filter(|lessons| !lessons.is_empty())
+                    .map(|lessons| (h, lessons.as_slice()))
+            })
+    }
+
+    /// Get first day after date that has any held lesson (skips weekends and holidays)
+    #[must_use]
+    pub fn next_school_day(&self, after: NaiveDate) -> Option<&Day> {
+        self.days.iter().find(|day| {
+            day.date.is_some_and(|date| date > after)
+                && day.lessons.iter().flatten().any(Lesson::is_held)
+        })
+    }
+
+    /// Get day and hour indices of periods without held lesson
+    #[must_use]
+    pub fn free_periods(&self) -> Vec<(usize, usize)> {
+        self.days
+            .iter()
+            .enumerate()
+            .flat_map(|(d, day)| {
+                (0..self.hours.len())
+                    .filter(move |&h| {
+                        !day.lessons
+                            .get(h)
+                            .is_some_and(|lessons| lessons.iter().any(Lesson::is_held))
+                    })
+                    .map(move |h| (d, h))
+            })
+            .collect()
+    }
+
+    /// Iterate over all lessons in timetable
+    fn lessons(&self) -> impl Iterator<Item = &Lesson> {
+        self.days
+            .iter()
+            .flat_map(|day| day.lessons.iter().flatten())
+    }
+
+    /// Iterate over all lessons with day and hour indices
+    pub fn lessons_with_times(&self) -> impl Iterator<Item = (usize, usize, &Lesson)> {
+        self.days.iter().enumerate().flat_map(|(d, day)| {
+            day.lessons
+                .iter()
+                .enumerate()
+                .flat_map(move |(h, lessons)| lessons.iter().map(move |lesson| (d, h, lesson)))
+        })
+    }
+
+    /// Group lessons by subject, with day and hour indices of each occurrence
+    ///
+    /// Canceled lessons are included when their subject is known, absences are skipped.
+    #[must_use]
+    pub fn by_subject(&self) -> HashMap<String, Vec<(usize, usize, &Lesson)>> {
+        let mut subjects = HashMap::<_, Vec<_>>::new();
+        for (d, h, lesson) in self.lessons_with_times() {
+            let subject = match lesson {
+                Lesson::Regular { subject, .. } | Lesson::Substitution { subject, .. } => subject,
+                Lesson::Canceled {
+                    subject: Some(subject),
+                    ..
+                } => subject,
+                Lesson::Canceled { subject: None, .. } | Lesson::Absent { .. } => continue,
+            };
+            subjects
+                .entry(subject.to_owned())
+                .or_default()
+                .push((d, h, lesson));
+        }
+        subjects
+    }
+
+    /// Collect sorted, deduplicated values from lessons
+    fn distinct<'a>(&'a self, f: impl Fn(&'a Lesson) -> Option<&'a String>) -> Vec<String> {
+        self.lessons()
+            .filter_map(f)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Get sorted list of distinct subjects of regular and substituted lessons
+    #[must_use]
+    pub fn subjects(&self) -> Vec<String> {
+        self.distinct(|lesson| match lesson {
+            Lesson::Regular { subject, .. } | Lesson::Substitution { subject, .. } => Some(subject),
+            _ => None,
+        })
+    }
+
+    /// Get sorted list of distinct teachers of regular and substituted lessons
+    #[must_use]
+    pub fn teachers(&self) -> Vec<String> {
+        self.distinct(|lesson| match lesson {
+            Lesson::Regular { teacher, .. } | Lesson::Substitution { teacher, .. } => Some(teacher),
+            _ => None,
+        })
+    }
+
+    /// Get sorted list of distinct week cycles (e.g. A/B weeks), empty if weeks don't alternate
+    #[must_use]
+    pub fn cycles(&self) -> Vec<String> {
+        self.distinct(|lesson| match lesson {
+            Lesson::Regular { cycle, .. } | Lesson::Substitution { cycle, .. } => cycle.as_ref(),
+            _ => None,
+        })
+    }
+
+    /// Get sorted list of distinct rooms of regular and substituted lessons
+    #[must_use]
+    pub fn rooms(&self) -> Vec<String> {
+        self.distinct(|lesson| match lesson {
+            Lesson::Regular { room, .. } | Lesson::Substitution { room, .. } => room.as_ref(),
+            _ => None,
+        })
+    }
+
+    /// Get school year (e.g. "2024/2025") from dates of days
+    ///
+    /// School year starts in September, `None` for timetables without dates (permanent)
+    #[must_use]
+    pub fn school_year(&self) -> Option<String> {
+        let date = self.days.iter().find_map(|day| day.date)?;
+        let start = if date.month() >= 9 {
+            date.year()
+        } else {
+            date.year() - 1
+        };
+        Some(format!("{start}/{}", start + 1))
+    }
+}
+
+/// Get day and hour indices of periods free in all timetables
+///
+/// # Errors
+/// If timetables have different hours
+pub fn common_free_periods(
+    timetables: &[Timetable],
+) -> Result<Vec<(usize, usize)>, ScheduleMismatch> {
+    let Some((first, rest)) = timetables.split_first() else {
+        return Ok(Vec::new());
+    };
+    if rest.iter().any(|timetable| timetable.hours != first.hours) {
+        return Err(ScheduleMismatch);
+    }
+
+    let free = rest
+        .iter()
+        .map(|timetable| timetable.free_periods().into_iter().collect::<HashSet<_>>())
+        .collect::<Vec<_>>();
+
+    Ok(first
+        .free_periods()
+        .into_iter()
+        .filter(|period| free.iter().all(|f| f.contains(period)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Lesson, Timetable, Type};
+    use chrono::{Datelike, Weekday};
+
+    const CLASS: &str = include_str!("../../fixtures/class.html");
+    const TEACHER: &str = include_str!("../../fixtures/teacher.html");
+    const ROOM: &str = include_str!("../../fixtures/room.html");
+
+    /// Number, start and end of hours
+    fn hours(timetable: &Timetable) -> Vec<(usize, String, String)> {
+        timetable
+            .hours
+            .iter()
+            .map(|hour| (hour.num, hour.start.to_string(), hour.end.to_string()))
+            .collect()
+    }
+
+    /// Day, month and weekday of days
+    fn days(timetable: &Timetable) -> Vec<(u32, u32, Option<Weekday>)> {
+        timetable
+            .days
+            .iter()
+            .map(|day| {
+                let date = day.date.unwrap();
+                assert_eq!(day.weekday, Some(date.weekday()));
+                (date.day(), date.month(), day.weekday)
+            })
+            .collect()
+    }
+
+    /// Subject, teacher, teacher abbreviation and class of held lessons in period
+    fn held(
+        timetable: &Timetable,
+        day: usize,
+        period: usize,
+    ) -> Vec<(&str, &str, Option<&str>, &str)> {
+        timetable.days[day].lessons[period]
+            .iter()
+            .filter_map(|lesson| match lesson {
+                Lesson::Regular {
+                    subject,
+                    teacher,
+                    teacher_abbr,
+                    class,
+                    ..
+                }
+                | Lesson::Substitution {
+                    subject,
+                    teacher,
+                    teacher_abbr,
+                    class,
+                    ..
+                } => Some((
+                    subject.as_str(),
+                    teacher.as_str(),
+                    teacher_abbr.as_deref(),
+                    class.as_str(),
+                )),
+                Lesson::Canceled { .. } | Lesson::Absent { .. } => None,
+            })
+            .collect()
+    }
+
+    fn assert_common(timetable: &Timetable) {
+        assert_eq!(
+            hours(timetable),
+            [
+                (1, "08:00:00".to_owned(), "08:45:00".to_owned()),
+                (2, "08:55:00".to_owned(), "09:40:00".to_owned())
+            ]
+        );
+        let dates = days(timetable);
+        assert!(dates.len() >= 2);
+        assert_eq!((dates[0].0, dates[0].1), (2, 9));
+        assert_eq!((dates[1].0, dates[1].1), (3, 9));
+        for day in &timetable.days {
+            assert_eq!(day.lessons.len(), 2);
+        }
+    }
+
+    #[test]
+    fn class_timetable() {
+        let timetable = Timetable::from_html(CLASS, &Type::Class("1.A".to_owned())).unwrap();
+        assert_common(&timetable);
+
+        assert_eq!(
+            held(&timetable, 0, 0),
+            [("Matematika", "Novák Jan", Some("Nov"), "1.A")]
+        );
+        // Parallel lessons are sorted by group
+        assert_eq!(
+            held(&timetable, 0, 1),
+            [
+                ("Anglický jazyk", "Svobodová Eva", Some("Svo"), "1.A"),
+                ("Německý jazyk", "Dvořák Petr", Some("Dvo"), "1.A")
+            ]
+        );
+
+        assert!(matches!(
+            timetable.days[1].lessons[0].as_slice(),
+            [Lesson::Substitution { subject, change: Some(change), .. }]
+                if subject == "Fyzika" && change.original_subject.as_deref() == Some("Chemie")
+        ));
+        assert!(matches!(
+            timetable.days[1].lessons[1].as_slice(),
+            [Lesson::Canceled { subject: Some(subject), .. }] if subject == "Dějepis"
+        ));
+        assert!(timetable.days[..2].iter().all(|day| day.events.is_empty()));
+
+        // Whole-day event instead of lessons
+        let wednesday = &timetable.days[2];
+        assert_eq!(wednesday.weekday, Some(wednesday.date.unwrap().weekday()));
+        assert_eq!(
+            wednesday.events,
+            [Event {
+                name: "Exkurze".to_owned(),
+                info: Some("Exkurze do Národního muzea, sraz v 7:45".to_owned()),
+            }]
+        );
+        assert!(wednesday.lessons.iter().all(Vec::is_empty));
+    }
+
+    /// Hours are looked up by number shown in header, not by position
+    #[test]
+    fn header_gap() {
+        let hours = [
+            (1, "8:00", "8:45"),
+            (2, "8:55", "9:40"),
+            (3, "10:00", "10:45"),
+            (4, "10:55", "11:40"),
+            (5, "11:50", "12:35"),
+            (7, "13:30", "14:15"),
+        ]
+        .iter()
+        .map(|(num, start, end)| {
+            format!(
+                r#"<div class="bk-hour-wrapper"><div class="num">{num}</div><div class="hour"><span>{start}</span><span> - </span><span>{end}</span></div></div>"#
+            )
+        })
+        .collect::<String>();
+        let cells = r#"<div class="bk-timetable-cell"></div>"#.repeat(5);
+        let html = format!(
+            r#"<div id="main"><div class="bk-timetable-hours">{hours}</div><div class="bk-timetable-row"><div class="bk-day-wrapper"><div><span class="bk-day-day">po</span><span class="bk-day-date">2.9.</span></div></div>{cells}<div class="bk-timetable-cell"><div class="day-item"><div class="day-item-hover" data-detail='{{"type":"atom","subjecttext":"Seminář | po 2.9. | 7 (13:30 - 14:15)","teacher":"Novák Jan","room":"101"}}'><div class="middle">Sem</div><div class="bottom">Nov</div></div></div></div></div></div>"#
+        );
+
+        let timetable = Timetable::from_html(&html, &Type::Class("1.A".to_owned())).unwrap();
+        assert!(timetable.validate().is_ok());
+        assert_eq!(timetable.hours.len(), 6);
+        assert!(timetable.hour(6).is_none());
+        let hour = timetable.hour(7).unwrap();
+        assert_eq!(
+            (hour.start.to_string(), hour.end.to_string()),
+            ("13:30:00".to_owned(), "14:15:00".to_owned())
+        );
+        // Period 5 is seventh hour
+        assert_eq!(timetable.hours[5], *hour);
+        assert_eq!(
+            held(&timetable, 0, 5),
+            [("Seminář", "Novák Jan", Some("Nov"), "1.A")]
+        );
+        assert!(timetable.days[0].lessons[..5].iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn teacher_timetable() {
+        let timetable = Timetable::from_html(TEACHER, &Type::Teacher("UXYZ".to_owned())).unwrap();
+        assert_common(&timetable);
+
+        // Name comes from page header, abbreviation isn't shown on teacher timetables
+        assert_eq!(
+            held(&timetable, 0, 0),
+            [("Matematika", "Novák Jan", None, "1.A")]
+        );
+        assert!(timetable.days[0].lessons[1].is_empty());
+        assert!(timetable.days[1].lessons[0].is_empty());
+        assert_eq!(
+            held(&timetable, 1, 1),
+            [("Matematika", "Novák Jan", None, "2.B")]
+        );
+    }
+
+    #[test]
+    fn room_timetable() {
+        let timetable = Timetable::from_html(ROOM, &Type::Room("101".to_owned())).unwrap();
+        assert_common(&timetable);
+
+        assert_eq!(
+            held(&timetable, 0, 0),
+            [("Matematika", "Novák Jan", Some("Nov"), "1.A")]
+        );
+        assert_eq!(
+            held(&timetable, 0, 1),
+            [("Chemie", "Dvořák Petr", Some("Dvo"), "3.C")]
+        );
+        assert!(timetable.days[1].lessons.iter().all(Vec::is_empty));
+    }
 }
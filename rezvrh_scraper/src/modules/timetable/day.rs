@@ -1,16 +1,37 @@
+use super::hour::{Hour, ParseError as HourParseError};
 use super::lesson::ParseError as LessonParseError;
-use super::{lesson::Lesson, util::single_iter, Type};
-use chrono::{Datelike, NaiveDate};
+use super::{
+    lesson::Lesson,
+    util::{date_without_year, single_iter},
+    Type,
+};
+use chrono::{Datelike, NaiveDate, Weekday};
 use once_cell::sync::Lazy;
-use scraper::{ElementRef, Selector};
+use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Struct that hold one day of timetable
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Day {
     pub date: Option<NaiveDate>,
+    pub weekday: Option<Weekday>,
+    /// Lessons of each period, multiple lessons in one period are parallel
+    /// (e.g. split groups) and are sorted by group and subject, canceled and absent last
     pub lessons: Vec<Vec<Lesson>>,
+    /// Whole-day events (e.g. school trips or days off) shown instead of lessons
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<Event>,
+}
+
+/// Whole-day event spanning all periods of the day
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
 }
 
 /// Day parse error
@@ -30,13 +51,212 @@ pub enum ParseError {
     Lesson(#[from] LessonParseError),
 }
 
+/// Layout of timetable rows and cells
+///
+/// Contents of the cells (date, lessons) are the same in both layouts.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Layout {
+    /// `div.bk-timetable-row` rows of `div.bk-timetable-cell` cells
+    Grid,
+    /// Rows of `table.bk-timetable`, used by older Bakalari versions
+    ///
+    /// Header row holds hours in `<th>` cells, every other row is a day with weekday
+    /// and date in leading `<th>` followed by `<td>` cells.
+    Table,
+}
+
 static DATE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("span.bk-day-date").unwrap());
-static CELL_SELECTOR: Lazy<Selector> =
+static WEEKDAY_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("span.bk-day-day").unwrap());
+static GRID_ROW_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("div.bk-timetable-row").unwrap());
+static GRID_CELL_SELECTOR: Lazy<Selector> =
     Lazy::new(|| Selector::parse("div.bk-timetable-cell").unwrap());
+static HOUR_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("div.bk-hour-wrapper").unwrap());
+static TABLE_ROW_SELECTOR: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse("table.bk-timetable > thead > tr, table.bk-timetable > tbody > tr").unwrap()
+});
+static EVENT_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("div.day-item-volno").unwrap());
+static EVENT_NAME_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("div.day-off").unwrap());
+
+impl Layout {
+    /// Detect layout of document, legacy table is used only if there is no grid
+    pub fn detect(document: &Html) -> Self {
+        if document.select(&GRID_ROW_SELECTOR).next().is_none()
+            && document.select(&TABLE_ROW_SELECTOR).next().is_some()
+        {
+            Self::Table
+        } else {
+            Self::Grid
+        }
+    }
+
+    /// Iterate over rows containing days (skips header rows of table)
+    pub fn rows(self, document: &Html) -> impl Iterator<Item = ElementRef<'_>> {
+        let selector = match self {
+            Self::Grid => &*GRID_ROW_SELECTOR,
+            Self::Table => &*TABLE_ROW_SELECTOR,
+        };
+        document
+            .select(selector)
+            .filter(move |row| self == Self::Grid || children(*row, "td").next().is_some())
+    }
+
+    /// Parse hours from header of timetable
+    pub fn hours(self, document: &Html) -> Result<Vec<Hour>, HourParseError> {
+        match self {
+            Self::Grid => document.select(&HOUR_SELECTOR).map(Hour::parse).collect(),
+            Self::Table => document
+                .select(&TABLE_ROW_SELECTOR)
+                .find(|row| children(*row, "td").next().is_none())
+                .into_iter()
+                .flat_map(|row| children(row, "th"))
+                // Corner cell above day names is empty
+                .filter(|cell| cell.text().any(|text| !text.trim().is_empty()))
+                .map(Hour::parse_header)
+                .collect(),
+        }
+    }
+
+    /// Get cells of lessons in row
+    fn cells(self, row: ElementRef) -> Vec<ElementRef> {
+        match self {
+            Self::Grid => row.select(&GRID_CELL_SELECTOR).collect(),
+            Self::Table => children(row, "td").collect(),
+        }
+    }
+}
+
+/// Iterate over child elements of element with given name
+fn children<'a>(element: ElementRef<'a>, name: &'a str) -> impl Iterator<Item = ElementRef<'a>> {
+    element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(move |child| child.value().name() == name)
+}
+
+/// Map localized weekday name (full or abbreviated) to weekday
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    match name.trim().to_lowercase().as_str() {
+        "po" | "pondělí" | "mo" | "mon" | "monday" => Some(Weekday::Mon),
+        "út" | "úterý" | "tu" | "tue" | "tuesday" => Some(Weekday::Tue),
+        "st" | "středa" | "we" | "wed" | "wednesday" => Some(Weekday::Wed),
+        "čt" | "čtvrtek" | "th" | "thu" | "thursday" => Some(Weekday::Thu),
+        "pá" | "pátek" | "fr" | "fri" | "friday" => Some(Weekday::Fri),
+        "so" | "sobota" | "sa" | "sat" | "saturday" => Some(Weekday::Sat),
+        "ne" | "neděle" | "su" | "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Key for sorting lessons in one period, by group and subject with held lessons first
+fn cell_order(lesson: &Lesson) -> (bool, Option<&str>, Option<&str>) {
+    match lesson {
+        Lesson::Regular { group, subject, .. } | Lesson::Substitution { group, subject, .. } => {
+            (false, group.as_deref(), Some(subject.as_str()))
+        }
+        Lesson::Canceled { subject, .. } => (true, None, subject.as_deref()),
+        Lesson::Absent { .. } => (true, None, None),
+    }
+}
 
 impl Day {
+    /// Create day of week timetable, weekday is taken from date
+    #[must_use]
+    pub fn new(date: NaiveDate, lessons: Vec<Vec<Lesson>>) -> Self {
+        Self {
+            date: Some(date),
+            weekday: Some(date.weekday()),
+            lessons,
+            events: Vec::new(),
+        }
+    }
+
+    /// Get date of day, `None` on permanent timetables
+    #[must_use]
+    pub const fn date(&self) -> Option<NaiveDate> {
+        self.date
+    }
+
+    /// Get lessons of each period
+    #[must_use]
+    pub fn lessons(&self) -> &[Vec<Lesson>] {
+        &self.lessons
+    }
+
+    /// Create day of permanent timetable (without date)
+    #[must_use]
+    pub const fn permanent(weekday: Weekday, lessons: Vec<Vec<Lesson>>) -> Self {
+        Self {
+            date: None,
+            weekday: Some(weekday),
+            lessons,
+            events: Vec::new(),
+        }
+    }
+
     /// Parse day from html
-    pub fn parse(day: ElementRef, timetable_type: &Type) -> Result<Self, ParseError> {
+    pub fn parse(
+        day: ElementRef,
+        layout: Layout,
+        timetable_type: &Type,
+        teacher_name: Option<&str>,
+    ) -> Result<Self, ParseError> {
+        let (date, weekday) = match layout {
+            Layout::Grid => Self::parse_grid_header(day)?,
+            Layout::Table => Self::parse_table_header(day)?,
+        };
+
+        let mut lessons = layout
+            .cells(day)
+            .into_iter()
+            .enumerate()
+            .map(|(period, lesson)| Lesson::parse(lesson, period, timetable_type, teacher_name))
+            .collect::<Result<Vec<_>, _>>()?;
+        // DOM order isn't stable, sort so that output doesn't change between fetches
+        for cell in &mut lessons {
+            cell.sort_by(|a, b| cell_order(a).cmp(&cell_order(b)));
+        }
+
+        let events = day
+            .select(&EVENT_SELECTOR)
+            .filter_map(|event| {
+                let name = event
+                    .select(&EVENT_NAME_SELECTOR)
+                    .next()
+                    .unwrap_or(event)
+                    .text()
+                    .collect::<String>();
+                let name = name.trim();
+                if name.is_empty() {
+                    return None;
+                }
+                let info = event
+                    .value()
+                    .attr("title")
+                    .map(str::trim)
+                    .filter(|info| !info.is_empty() && *info != name)
+                    .map(ToOwned::to_owned);
+                Some(Event {
+                    name: name.to_owned(),
+                    info,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            date,
+            weekday,
+            lessons,
+            events,
+        })
+    }
+
+    /// Parse date and weekday from `span.bk-day-date` and `span.bk-day-day` of grid row
+    fn parse_grid_header(
+        day: ElementRef,
+    ) -> Result<(Option<NaiveDate>, Option<Weekday>), ParseError> {
         let mut dates = single_iter(day.select(&DATE_SELECTOR), || ParseError::NoDate)?.text();
         let date = dates.next().map(|d| d.trim().to_owned());
         if date.is_some() && dates.next().is_some() {
@@ -44,46 +264,152 @@ impl Day {
         }
 
         let date = date
-            .map(|d| {
-                // Current year
-                let year = chrono::Local::now().date_naive().year();
-                // 12.6.
-                let (day, month) = d.split_once('.').ok_or(ParseError::ParseDate(d.clone()))?;
-                let (month, _) = month
-                    .split_once('.')
-                    .ok_or(ParseError::ParseDate(d.clone()))?;
-                let date = NaiveDate::from_ymd_opt(
-                    year,
-                    month
-                        .parse()
-                        .map_err(|_| ParseError::ParseDate(d.clone()))?,
-                    day.parse().map_err(|_| ParseError::ParseDate(d.clone()))?,
-                )
-                .ok_or(ParseError::ParseDate(d.clone()))?;
-
-                // Check diff by months
-                let now = chrono::Local::now().date_naive();
-                let diff = date - now;
-                let date = if diff.num_days() < -60 {
-                    // Next year
-                    NaiveDate::from_ymd_opt(year + 1, date.month(), date.day())
-                        .ok_or(ParseError::ParseDate(d.clone()))?
-                } else if diff.num_days() > 60 {
-                    // Last year
-                    NaiveDate::from_ymd_opt(year - 1, date.month(), date.day())
-                        .ok_or(ParseError::ParseDate(d.clone()))?
-                } else {
-                    date
-                };
-                Ok::<_, ParseError>(date)
-            })
+            .map(|d| date_without_year(&d).ok_or(ParseError::ParseDate(d)))
             .transpose()?;
 
-        let lessons = day
-            .select(&CELL_SELECTOR)
-            .map(|lesson| Lesson::parse(lesson, timetable_type))
-            .collect::<Result<Vec<_>, _>>()?;
+        // Permanent timetables have only name of the day
+        let weekday = date.map(|date| date.weekday()).or_else(|| {
+            day.select(&WEEKDAY_SELECTOR)
+                .next()
+                .and_then(|name| name.text().next())
+                .and_then(parse_weekday)
+        });
+
+        Ok((date, weekday))
+    }
+
+    /// Parse date and weekday from leading `<th>` of table row, e.g. `<th>po<br>2.9.</th>`
+    ///
+    /// Permanent timetables have only name of the day.
+    fn parse_table_header(
+        day: ElementRef,
+    ) -> Result<(Option<NaiveDate>, Option<Weekday>), ParseError> {
+        let header = children(day, "th").next().ok_or(ParseError::NoName)?;
+        let text = header.text().collect::<Vec<_>>().join(" ");
+        let mut date = None;
+        let mut weekday = None;
+        for token in text.split_whitespace() {
+            if let Some(parsed) = parse_weekday(token) {
+                weekday = Some(parsed);
+            } else if token.contains('.') {
+                date = Some(
+                    date_without_year(token)
+                        .ok_or_else(|| ParseError::ParseDate(token.to_owned()))?,
+                );
+            }
+        }
+        if date.is_none() && weekday.is_none() {
+            return Err(ParseError::NoNameText);
+        }
+
+        Ok((date, date.map(|date| date.weekday()).or(weekday)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Layout;
+    use crate::modules::timetable::{Lesson, Timetable, Type};
+    use chrono::{Datelike, NaiveTime, Weekday};
+    use scraper::Html;
+
+    const LEGACY: &str = r#"<html><body>
+<table class="legend"><tr><td>not a timetable</td></tr></table>
+<table class="bk-timetable">
+  <thead>
+    <tr><th></th><th>1<br>8:00 - 8:45</th><th>2<br>8:55 - 9:40</th></tr>
+  </thead>
+  <tbody>
+    <tr>
+      <th>po<br>2.9.</th>
+      <td><div class="day-item"><div class="day-item-hover" data-detail='{"type":"atom","subjecttext":"Matematika | po 2.9. | 1 (8:00 - 8:45)","teacher":"Novák Jan","room":"101"}'><div class="middle">M</div><div class="bottom">Nov</div></div></div></td>
+      <td></td>
+    </tr>
+    <tr>
+      <th>út<br>3.9.</th>
+      <td></td>
+      <td><div class="day-item"><div class="day-item-hover" data-detail='{"type":"atom","subjecttext":"Fyzika","teacher":"Dvořák Petr","room":"202"}'><div class="middle">F</div><div class="bottom">Dvo</div></div></div></td>
+    </tr>
+  </tbody>
+</table>
+</body></html>"#;
+
+    #[test]
+    fn detects_legacy_table() {
+        assert_eq!(Layout::detect(&Html::parse_document(LEGACY)), Layout::Table);
+        assert_eq!(
+            Layout::detect(&Html::parse_document(
+                "<table><tr><td>unrelated</td></tr></table>"
+            )),
+            Layout::Grid
+        );
+    }
+
+    #[test]
+    fn parses_legacy_table() {
+        let timetable = Timetable::from_html(LEGACY, &Type::Class("1.A".to_owned())).unwrap();
+
+        let hours = timetable
+            .hours
+            .iter()
+            .map(|hour| (hour.num, hour.start, hour.end))
+            .collect::<Vec<_>>();
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(
+            hours,
+            [(1, time(8, 0), time(8, 45)), (2, time(8, 55), time(9, 40))]
+        );
+
+        assert_eq!(timetable.days.len(), 2);
+        let dates = timetable
+            .days
+            .iter()
+            .map(|day| {
+                let date = day.date.unwrap();
+                (date.day(), date.month(), day.weekday)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dates,
+            [(2, 9, Some(date_weekday(2))), (3, 9, Some(date_weekday(3)))]
+        );
+
+        let monday = &timetable.days[0].lessons;
+        assert_eq!(monday.len(), 2);
+        assert!(matches!(
+            monday[0].as_slice(),
+            [Lesson::Regular { subject, teacher_abbr: Some(abbr), room: Some(room), .. }]
+                if subject == "Matematika" && abbr == "Nov" && room == "101"
+        ));
+        assert!(monday[1].is_empty());
+
+        let tuesday = &timetable.days[1].lessons;
+        assert!(tuesday[0].is_empty());
+        assert!(matches!(
+            tuesday[1].as_slice(),
+            [Lesson::Regular { subject, period: 1, .. }] if subject == "Fyzika"
+        ));
+    }
+
+    #[test]
+    fn parses_permanent_legacy_header() {
+        let html = LEGACY.replace("<br>2.9.", "").replace("<br>3.9.", "");
+        let timetable = Timetable::from_html(&html, &Type::Class("1.A".to_owned())).unwrap();
+        let days = timetable
+            .days
+            .iter()
+            .map(|day| (day.date, day.weekday))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            days,
+            [(None, Some(Weekday::Mon)), (None, Some(Weekday::Tue))]
+        );
+    }
 
-        Ok(Self { date, lessons })
+    /// Weekday of September date in year picked by parser
+    fn date_weekday(day: u32) -> Weekday {
+        crate::modules::timetable::util::date_without_year(&format!("{day}.9."))
+            .unwrap()
+            .weekday()
     }
 }
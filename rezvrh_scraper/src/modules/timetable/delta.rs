@@ -0,0 +1,73 @@
+use super::{day::Day, Lesson, Timetable};
+use serde::{Deserialize, Serialize};
+
+/// Change of student's schedule against permanent timetable
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Delta {
+    /// No lessons are held on the day
+    FreeDay { day: usize },
+    /// First lesson is later than usual
+    LaterStart { day: usize, new_first_period: usize },
+    /// Last lesson is earlier than usual
+    EarlierEnd { day: usize, new_last_period: usize },
+    /// Period between lessons is free, while it usually isn't
+    MidDayGap { day: usize, period: usize },
+}
+
+/// Get indices of periods with held lesson
+fn held_periods(day: &Day) -> Vec<usize> {
+    day.lessons
+        .iter()
+        .enumerate()
+        .filter(|(_, lessons)| lessons.iter().any(Lesson::is_held))
+        .map(|(period, _)| period)
+        .collect()
+}
+
+impl Timetable {
+    /// Compare timetable with permanent one, reporting extra free time
+    ///
+    /// Days are matched by weekday, periods by index. Day indices refer to this timetable.
+    #[must_use]
+    pub fn schedule_deltas(&self, permanent: &Self) -> Vec<Delta> {
+        let mut deltas = Vec::new();
+        for (d, day) in self.days.iter().enumerate() {
+            let Some(usual) = day
+                .weekday
+                .and_then(|weekday| permanent.day_by_weekday(weekday))
+            else {
+                continue;
+            };
+            let usual = held_periods(usual);
+            let (Some(&usual_first), Some(&usual_last)) = (usual.first(), usual.last()) else {
+                continue;
+            };
+
+            let held = held_periods(day);
+            let (Some(&first), Some(&last)) = (held.first(), held.last()) else {
+                deltas.push(Delta::FreeDay { day: d });
+                continue;
+            };
+
+            if first > usual_first {
+                deltas.push(Delta::LaterStart {
+                    day: d,
+                    new_first_period: first,
+                });
+            }
+            if last < usual_last {
+                deltas.push(Delta::EarlierEnd {
+                    day: d,
+                    new_last_period: last,
+                });
+            }
+            deltas.extend(
+                (first..last)
+                    .filter(|period| usual.contains(period) && !held.contains(period))
+                    .map(|period| Delta::MidDayGap { day: d, period }),
+            );
+        }
+        deltas
+    }
+}
@@ -0,0 +1,105 @@
+use super::{Lesson, Timetable};
+use serde::{Deserialize, Serialize};
+
+/// Change of lesson against permanent timetable
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum LessonChange {
+    /// Lesson isn't in permanent timetable
+    Added {
+        day: usize,
+        period: usize,
+        lesson: Lesson,
+    },
+    /// Lesson of permanent timetable is missing
+    Removed {
+        day: usize,
+        period: usize,
+        lesson: Lesson,
+    },
+    /// Lesson differs from permanent one (including cancellation)
+    Changed {
+        day: usize,
+        period: usize,
+        old: Box<Lesson>,
+        new: Box<Lesson>,
+    },
+}
+
+/// Compare lessons of one period
+fn diff_cell(day: usize, period: usize, usual: &[Lesson], actual: &[Lesson]) -> Vec<LessonChange> {
+    let mut changes = Vec::new();
+    let mut usual = usual.iter().map(Some).collect::<Vec<_>>();
+    let mut unmatched = Vec::new();
+
+    // Same lesson, possibly with changed details (e.g. room)
+    for lesson in actual {
+        let key = lesson.slot_key();
+        let found = usual
+            .iter_mut()
+            .find(|old| old.is_some_and(|old| old.slot_key() == key))
+            .and_then(Option::take);
+        match found {
+            Some(old) if old == lesson => {}
+            Some(old) => changes.push(LessonChange::Changed {
+                day,
+                period,
+                old: Box::new(old.clone()),
+                new: Box::new(lesson.clone()),
+            }),
+            None => unmatched.push(lesson),
+        }
+    }
+
+    // Cancellations without details replace any remaining usual lesson
+    let mut remaining = usual.into_iter().flatten();
+    for lesson in unmatched {
+        let old = matches!(lesson, Lesson::Canceled { .. })
+            .then(|| remaining.next())
+            .flatten();
+        changes.push(match old {
+            Some(old) => LessonChange::Changed {
+                day,
+                period,
+                old: Box::new(old.clone()),
+                new: Box::new(lesson.clone()),
+            },
+            None => LessonChange::Added {
+                day,
+                period,
+                lesson: lesson.clone(),
+            },
+        });
+    }
+    changes.extend(remaining.map(|lesson| LessonChange::Removed {
+        day,
+        period,
+        lesson: lesson.clone(),
+    }));
+
+    changes
+}
+
+/// Compare actual timetable with permanent one
+///
+/// Days are matched by weekday, periods by index. Day indices refer to actual timetable,
+/// lessons within period are matched by [`Lesson::slot_key`].
+#[must_use]
+pub fn diff(permanent: &Timetable, actual: &Timetable) -> Vec<LessonChange> {
+    let mut changes = Vec::new();
+    for (d, day) in actual.days.iter().enumerate() {
+        let Some(usual) = day
+            .weekday
+            .and_then(|weekday| permanent.day_by_weekday(weekday))
+        else {
+            continue;
+        };
+        let periods = day.lessons.len().max(usual.lessons.len());
+        for period in 0..periods {
+            let usual = usual.lessons.get(period).map_or(&[][..], Vec::as_slice);
+            let actual = day.lessons.get(period).map_or(&[][..], Vec::as_slice);
+            changes.extend(diff_cell(d, period, usual, actual));
+        }
+    }
+    changes
+}
@@ -0,0 +1,50 @@
+use super::{Lesson, Timetable};
+use serde_json::{json, Value};
+
+impl Timetable {
+    /// Convert lessons of dated days to FullCalendar events
+    ///
+    /// Events have `title`, `start`, `end` and `extendedProps` (the whole lesson),
+    /// canceled lessons get `className` `canceled` so they can be struck-through.
+    /// Days without date (permanent timetable) are skipped.
+    #[must_use]
+    pub fn to_fullcalendar_events(&self) -> Vec<Value> {
+        self.lessons_with_times()
+            .filter_map(|(d, h, lesson)| {
+                let date = self.days[d].date?;
+                let hour = self.hours.get(h)?;
+                let start = date.and_time(hour.start).format("%Y-%m-%dT%H:%M:%S");
+                let end = date.and_time(hour.end).format("%Y-%m-%dT%H:%M:%S");
+
+                let mut event = json!({
+                    "title": title(lesson),
+                    "start": start.to_string(),
+                    "end": end.to_string(),
+                    "extendedProps": lesson,
+                });
+                if let Lesson::Canceled { .. } = lesson {
+                    event["className"] = json!("canceled");
+                }
+                Some(event)
+            })
+            .collect()
+    }
+}
+
+/// Title of event shown in calendar
+fn title(lesson: &Lesson) -> String {
+    match lesson {
+        Lesson::Regular { subject, room, .. } | Lesson::Substitution { subject, room, .. } => {
+            match room {
+                Some(room) => format!("{subject} ({room})"),
+                None => subject.to_owned(),
+            }
+        }
+        Lesson::Canceled {
+            subject: Some(subject),
+            ..
+        } => format!("{subject} (canceled)"),
+        Lesson::Canceled { .. } => "Canceled".to_owned(),
+        Lesson::Absent { info, .. } => info.to_owned(),
+    }
+}
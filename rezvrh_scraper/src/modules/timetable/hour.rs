@@ -1,5 +1,5 @@
 use super::util::single_iter;
-use chrono::NaiveTime;
+use chrono::{Duration, NaiveTime};
 use once_cell::sync::Lazy;
 use scraper::{ElementRef, Selector};
 use serde::{Deserialize, Serialize};
@@ -7,8 +7,13 @@ use thiserror::Error;
 
 /// Struct that hold one hour of timetable (header)
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Hour {
+    /// Number of hour as shown by Bakalari (might not be contiguous)
+    pub num: usize,
     pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Duration in minutes
     pub duration: u32,
 }
 
@@ -21,8 +26,6 @@ pub enum ParseError {
     NoNumText,
     #[error("failed to parse number: {0}")]
     ParseNum(std::num::ParseIntError),
-    #[error("mismatched number")]
-    MismatchedNum,
     #[error("no from")]
     NoFrom,
     #[error("no from text")]
@@ -35,8 +38,8 @@ pub enum ParseError {
     NoTo,
     #[error("no to text")]
     NoToText,
-    #[error("inverted from and to")]
-    InvertedFromTo,
+    #[error("hour doesn't end after it starts")]
+    NonPositiveDuration,
     #[error("failed to parse to: {0}")]
     ParseTo(chrono::ParseError),
 }
@@ -45,15 +48,40 @@ static NUM_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("div.num").un
 static TIMES_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("div.hour > span").unwrap());
 
 impl Hour {
+    /// Create hour starting at time, lasting duration in minutes
+    #[must_use]
+    pub fn new(num: usize, start: NaiveTime, duration: u32) -> Self {
+        Self {
+            num,
+            start,
+            end: start + Duration::minutes(duration.into()),
+            duration,
+        }
+    }
+
+    /// Get start time
+    #[must_use]
+    pub const fn start(&self) -> NaiveTime {
+        self.start
+    }
+
+    /// Get duration in minutes
+    #[must_use]
+    pub fn duration(&self) -> i64 {
+        i64::from(self.duration)
+    }
+
+    /// Get end time
+    #[must_use]
+    pub const fn end(&self) -> NaiveTime {
+        self.end
+    }
+
     /// Parse hour from html
-    pub fn parse(hour: ElementRef, i: usize) -> Result<Self, ParseError> {
+    pub fn parse(hour: ElementRef) -> Result<Self, ParseError> {
         let num = single_iter(hour.select(&NUM_SELECTOR), || ParseError::NoNum)?;
         let num = single_iter(num.text(), || ParseError::NoNumText)?;
-        let num = num.parse::<usize>().map_err(ParseError::ParseNum)?;
-        if num != i + 1 {
-            println!("num: {num}, i: {i}");
-            //return Err(ParseError::MismatchedNum);
-        }
+        let num = num.trim().parse::<usize>().map_err(ParseError::ParseNum)?;
 
         let mut times = hour.select(&TIMES_SELECTOR);
         let from = times.next().ok_or(ParseError::NoFrom)?;
@@ -65,12 +93,41 @@ impl Hour {
 
         let to = single_iter(to.text(), || ParseError::NoToText)?;
         let to = NaiveTime::parse_from_str(to, "%H:%M").map_err(ParseError::ParseTo)?;
-        let duration = to - from;
-        let duration = duration.num_minutes();
-        let duration = u32::try_from(duration).map_err(|_| ParseError::InvertedFromTo)?;
+        if to <= from {
+            return Err(ParseError::NonPositiveDuration);
+        }
+        let duration = (to - from).num_minutes();
+        let duration = u32::try_from(duration).map_err(|_| ParseError::NonPositiveDuration)?;
+
+        Ok(Self {
+            num,
+            start: from,
+            end: to,
+            duration,
+        })
+    }
+
+    /// Parse hour from header cell of legacy table layout, e.g. `<th>1<br>8:00 - 8:45</th>`
+    pub fn parse_header(cell: ElementRef) -> Result<Self, ParseError> {
+        let text = cell.text().collect::<Vec<_>>().join(" ").replace('-', " ");
+        let mut tokens = text.split_whitespace();
+        let num = tokens.next().ok_or(ParseError::NoNum)?;
+        let num = num.parse::<usize>().map_err(ParseError::ParseNum)?;
+
+        let from = tokens.next().ok_or(ParseError::NoFrom)?;
+        let from = NaiveTime::parse_from_str(from, "%H:%M").map_err(ParseError::ParseFrom)?;
+        let to = tokens.next().ok_or(ParseError::NoTo)?;
+        let to = NaiveTime::parse_from_str(to, "%H:%M").map_err(ParseError::ParseTo)?;
+        if to <= from {
+            return Err(ParseError::NonPositiveDuration);
+        }
+        let duration = u32::try_from((to - from).num_minutes())
+            .map_err(|_| ParseError::NonPositiveDuration)?;
 
         Ok(Self {
+            num,
             start: from,
+            end: to,
             duration,
         })
     }
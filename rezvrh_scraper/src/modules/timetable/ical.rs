@@ -0,0 +1,167 @@
+use super::{Lesson, Timetable};
+use chrono::{Datelike, Duration, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::fmt::Write;
+
+/// Escape text value (RFC 5545, section 3.3.11)
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold content line to at most 75 octets per line
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Summary, description and location of event, `None` for canceled lessons
+fn summary(lesson: &Lesson) -> Option<(String, Option<String>, Option<&str>)> {
+    match lesson {
+        Lesson::Regular {
+            subject,
+            teacher,
+            room,
+            ..
+        } => Some((
+            subject.to_owned(),
+            Some(teacher.to_owned()),
+            room.as_deref(),
+        )),
+        Lesson::Substitution {
+            subject,
+            teacher,
+            room,
+            ..
+        } => Some((
+            format!("{subject} (substitution)"),
+            Some(teacher.to_owned()),
+            room.as_deref(),
+        )),
+        Lesson::Canceled { .. } => None,
+        Lesson::Absent { info, .. } => Some((info.to_owned(), None, None)),
+    }
+}
+
+/// Stable part of event UID, from subject and group of lesson
+///
+/// Doesn't depend on other lessons or volatile fields (room, teacher), so that
+/// subscribed calendars update the event instead of replacing it.
+fn uid_key(lesson: &Lesson) -> String {
+    let (subject, group) = match lesson {
+        Lesson::Regular { subject, group, .. } | Lesson::Substitution { subject, group, .. } => {
+            (subject.as_str(), group.as_deref())
+        }
+        Lesson::Canceled { subject, .. } => (subject.as_deref().unwrap_or_default(), None),
+        Lesson::Absent { info, .. } => (info.as_str(), None),
+    };
+    format!("{subject}-{}", group.unwrap_or_default())
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Format time in UTC, falls back to floating time if it doesn't exist in `tz`
+fn utc_time(time: NaiveDateTime, tz: Tz) -> String {
+    tz.from_local_datetime(&time).earliest().map_or_else(
+        || time.format("%Y%m%dT%H%M%S").to_string(),
+        |time| {
+            time.with_timezone(&Utc)
+                .format("%Y%m%dT%H%M%SZ")
+                .to_string()
+        },
+    )
+}
+
+/// Map weekday to RRULE day
+const fn rrule_day(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+impl Timetable {
+    /// Convert lessons to iCalendar (`text/calendar`), canceled lessons are skipped
+    ///
+    /// Lessons of dated days are emitted in UTC, converted from local time in `tz`.
+    /// Days without date (permanent timetable) are emitted as weekly recurring events
+    /// starting this week (in `tz`), with floating times, so that they stay at the same
+    /// wall-clock time across daylight saving changes without a `VTIMEZONE`.
+    #[must_use]
+    pub fn to_ical(&self, tz: Tz) -> String {
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let monday = {
+            let today = Utc::now().with_timezone(&tz).date_naive();
+            today - Duration::days(i64::from(today.weekday().num_days_from_monday()))
+        };
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_owned(),
+            "VERSION:2.0".to_owned(),
+            "PRODID:-//rezvrh//rezvrh_scraper//EN".to_owned(),
+            format!("X-WR-TIMEZONE:{}", tz.name()),
+        ];
+        for (d, h, lesson) in self.lessons_with_times() {
+            let day = &self.days[d];
+            let Some(hour) = self.hours.get(h) else {
+                continue;
+            };
+            let Some((summary, description, location)) = summary(lesson) else {
+                continue;
+            };
+            let key = uid_key(lesson);
+            let times = if let Some(date) = day.date {
+                vec![
+                    format!("UID:{}-{}-{key}@rezvrh", date.format("%Y%m%d"), hour.num),
+                    format!("DTSTART:{}", utc_time(date.and_time(hour.start), tz)),
+                    format!("DTEND:{}", utc_time(date.and_time(hour.end), tz)),
+                ]
+            } else if let Some(weekday) = day.weekday {
+                let date = monday + Duration::days(i64::from(weekday.num_days_from_monday()));
+                let local = |time| date.and_time(time).format("%Y%m%dT%H%M%S");
+                vec![
+                    format!("UID:{}-{}-{key}@rezvrh", rrule_day(weekday), hour.num),
+                    format!("DTSTART:{}", local(hour.start)),
+                    format!("DTEND:{}", local(hour.end)),
+                    format!("RRULE:FREQ=WEEKLY;BYDAY={}", rrule_day(weekday)),
+                ]
+            } else {
+                continue;
+            };
+            lines.push("BEGIN:VEVENT".to_owned());
+            lines.push(format!("DTSTAMP:{stamp}"));
+            lines.extend(times);
+            lines.push(format!("SUMMARY:{}", escape(&summary)));
+            if let Some(description) = description {
+                lines.push(format!("DESCRIPTION:{}", escape(&description)));
+            }
+            if let Some(location) = location {
+                lines.push(format!("LOCATION:{}", escape(location)));
+            }
+            lines.push("END:VEVENT".to_owned());
+        }
+        lines.push("END:VCALENDAR".to_owned());
+
+        lines.iter().fold(String::new(), |mut ical, line| {
+            let _ = write!(ical, "{}", fold(line));
+            ical
+        })
+    }
+}
@@ -1,90 +1,198 @@
-use self::parser::subject;
+use self::parser::{subject, SubjectMeta};
 
 use super::{
-    util::{empty_string_as_none, single_iter},
+    util::{bool_or_string, empty_string_as_none, single_iter},
     Type,
 };
+use chrono::{NaiveDate, NaiveTime};
 use once_cell::sync::Lazy;
 use scraper::{Element, ElementRef, Selector};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+mod change;
+mod group;
 mod parser;
 
+pub use change::ChangeInfo;
+pub use group::Group;
+
 /// Struct that hold one lesson of timetable
+///
+/// Fields are serialized in camelCase (e.g. `teacherAbbr`), `None` fields are omitted
+/// (except `homeworks`, which is always present)
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase", rename_all_fields = "camelCase")]
 pub enum Lesson {
     Regular {
+        /// Index of period (column) in timetable, matching index in `Timetable::hours`
+        period: usize,
         class: String,
+        /// Full name of the subject (e.g. `Matematika`), taken from `subjecttext`
         subject: String,
+        /// Abbreviation of the subject (e.g. `M`), as shown in timetable cell
         abbr: String,
+        /// Date of lesson from its own description, `None` on permanent timetables
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        date: Option<NaiveDate>,
+        /// Start and end time of lesson from its own description
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        time: Option<(NaiveTime, NaiveTime)>,
+        /// Display name of the teacher
         teacher: String,
+        /// Abbreviation of the teacher, `None` on teacher timetables
+        #[serde(skip_serializing_if = "Option::is_none")]
         teacher_abbr: Option<String>,
+        /// Abbreviations of other teachers, if lesson is taught by more of them
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        additional_teachers: Vec<String>,
+        /// Room of lesson, `None` for lessons without room (e.g. online lessons)
+        #[serde(skip_serializing_if = "Option::is_none")]
         room: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         group: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         topic: Option<String>,
+        /// Assigned homework, serialized as `null` when there is none
+        homeworks: Option<String>,
+        /// Week cycle of lesson on permanent timetables with alternating weeks
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cycle: Option<String>,
+        /// Note attached to lesson
+        #[serde(skip_serializing_if = "Option::is_none")]
+        notice: Option<String>,
+        /// Text describing change of lesson (e.g. `Suplování (Novák)`)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        change_info: Option<String>,
+        /// Whether absence was recorded in lesson
+        #[serde(default)]
+        has_absent: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        absence_text: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        absent_info_text: Option<String>,
     },
     Substitution {
+        /// Index of period (column) in timetable, matching index in `Timetable::hours`
+        period: usize,
         class: String,
+        /// Full name of the subject (e.g. `Matematika`), taken from `subjecttext`
         subject: String,
+        /// Abbreviation of the subject (e.g. `M`), as shown in timetable cell
         abbr: String,
+        /// Date of lesson from its own description, `None` on permanent timetables
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        date: Option<NaiveDate>,
+        /// Start and end time of lesson from its own description
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        time: Option<(NaiveTime, NaiveTime)>,
+        /// Display name of the teacher
         teacher: String,
+        /// Abbreviation of the teacher, `None` on teacher timetables
+        #[serde(skip_serializing_if = "Option::is_none")]
         teacher_abbr: Option<String>,
+        /// Abbreviations of other teachers, if lesson is taught by more of them
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        additional_teachers: Vec<String>,
+        /// Room of lesson, `None` for lessons without room (e.g. online lessons)
+        #[serde(skip_serializing_if = "Option::is_none")]
         room: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         group: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         topic: Option<String>,
+        /// Assigned homework, serialized as `null` when there is none
+        homeworks: Option<String>,
+        /// Week cycle of lesson on permanent timetables with alternating weeks
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cycle: Option<String>,
+        /// Note attached to lesson
+        #[serde(skip_serializing_if = "Option::is_none")]
+        notice: Option<String>,
+        /// Text describing change of lesson (e.g. `Suplování (Novák)`)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        change_info: Option<String>,
+        /// Whether absence was recorded in lesson
+        #[serde(default)]
+        has_absent: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        absence_text: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        absent_info_text: Option<String>,
+        /// What was changed (original teacher, subject and reason), if Bakalari says
+        #[serde(skip_serializing_if = "Option::is_none")]
+        change: Option<ChangeInfo>,
+    },
+    /// Canceled lesson, with details of the original lesson if Bakalari provides them
+    Canceled {
+        /// Index of period (column) in timetable, matching index in `Timetable::hours`
+        period: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        subject: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        teacher: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
     },
-    Canceled,
     Absent {
+        /// Index of period (column) in timetable, matching index in `Timetable::hours`
+        period: usize,
         info: String,
         abbr: String,
     },
 }
 
 /// Data that is stored in data-detail attribute
+///
+/// All fields except the tag are optional, so that missing fields don't fail the whole timetable
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 enum LessonData {
     #[serde(rename = "atom")]
     Regular {
         #[serde(rename = "subjecttext")]
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         subject_text: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         teacher: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         room: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         group: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         theme: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         notice: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         changeinfo: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         homeworks: Option<String>,
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         absencetext: Option<String>,
-        #[serde(rename = "hasAbsent")]
+        #[serde(rename = "hasAbsent", default, deserialize_with = "bool_or_string")]
         has_absent: bool,
         #[serde(rename = "absentInfoText")]
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         absent_info_text: Option<String>,
+        #[serde(default, deserialize_with = "empty_string_as_none")]
+        cycle: Option<String>,
     },
     #[serde(rename = "removed")]
     Canceled {
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         subjecttext: Option<String>,
+        #[serde(default, deserialize_with = "empty_string_as_none")]
+        teacher: Option<String>,
+        #[serde(default, deserialize_with = "empty_string_as_none")]
+        room: Option<String>,
     },
     #[serde(rename = "absent")]
     Absent {
         #[serde(rename = "InfoAbsentName")]
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         info_absent_name: Option<String>,
         #[serde(rename = "absentinfo")]
-        #[serde(deserialize_with = "empty_string_as_none")]
+        #[serde(default, deserialize_with = "empty_string_as_none")]
         absent_info: Option<String>,
     },
 }
@@ -120,7 +228,12 @@ fn get_prop(
     Ok(elem.trim().to_owned())
 }
 
-fn parse_single(lesson: ElementRef, timetable_type: &Type) -> Result<Lesson, ParseError> {
+fn parse_single(
+    lesson: ElementRef,
+    period: usize,
+    timetable_type: &Type,
+    teacher_name: Option<&str>,
+) -> Result<Lesson, ParseError> {
     let data = lesson
         .value()
         .attr("data-detail")
@@ -134,23 +247,29 @@ fn parse_single(lesson: ElementRef, timetable_type: &Type) -> Result<Lesson, Par
             room,
             group,
             theme,
-            notice: _,
-            changeinfo: _,
-            homeworks: _,
-            absencetext: _,
-            has_absent: _,
-            absent_info_text: _,
+            notice,
+            changeinfo,
+            homeworks,
+            absencetext,
+            has_absent,
+            absent_info_text,
+            cycle,
         } => {
             let substituion = lesson.has_class(
                 &"pink".into(),
                 scraper::CaseSensitivity::AsciiCaseInsensitive,
             );
 
-            let subject = subject(subject_text)?;
+            let SubjectMeta {
+                subject,
+                date,
+                time,
+            } = subject(subject_text)?;
 
             let abbr = get_prop(lesson, &ABBR_SELECTOR, "abbr")?;
 
-            let (teacher, teacher_abbr) = parser::teacher(lesson, teacher, timetable_type)?;
+            let (teacher, teacher_abbr, additional_teachers) =
+                parser::teacher(lesson, teacher, timetable_type, teacher_name)?;
 
             let topic = theme;
 
@@ -165,25 +284,48 @@ fn parse_single(lesson: ElementRef, timetable_type: &Type) -> Result<Lesson, Par
 
             if substituion {
                 Ok(Lesson::Substitution {
+                    period,
                     class,
                     subject,
                     abbr,
+                    date,
+                    time,
                     teacher,
                     teacher_abbr,
+                    additional_teachers,
                     room,
                     group,
                     topic,
+                    homeworks,
+                    cycle,
+                    change: changeinfo.as_deref().map(ChangeInfo::parse),
+                    notice,
+                    change_info: changeinfo,
+                    has_absent,
+                    absence_text: absencetext,
+                    absent_info_text,
                 })
             } else {
                 Ok(Lesson::Regular {
+                    period,
                     class,
                     subject,
                     abbr,
+                    date,
+                    time,
                     teacher,
                     teacher_abbr,
+                    additional_teachers,
                     room,
                     group,
                     topic,
+                    homeworks,
+                    cycle,
+                    notice,
+                    change_info: changeinfo,
+                    has_absent,
+                    absence_text: absencetext,
+                    absent_info_text,
                 })
             }
         }
@@ -200,17 +342,26 @@ fn parse_single(lesson: ElementRef, timetable_type: &Type) -> Result<Lesson, Par
 
                 let abbr = absent_info.ok_or(ParseError::MissingProperty("absent_info"))?;
 
-                Ok(Lesson::Absent { info, abbr })
+                Ok(Lesson::Absent { period, info, abbr })
             } else {
                 Err(ParseError::DataTypeMismatch)
             }
         }
-        LessonData::Canceled { subjecttext: _ } => {
+        LessonData::Canceled {
+            subjecttext,
+            teacher,
+            room,
+        } => {
             if lesson.has_class(
                 &"pink".into(),
                 scraper::CaseSensitivity::AsciiCaseInsensitive,
             ) {
-                Ok(Lesson::Canceled)
+                Ok(Lesson::Canceled {
+                    period,
+                    subject: subject(subjecttext).ok().map(|meta| meta.subject),
+                    teacher,
+                    room,
+                })
             } else {
                 Err(ParseError::DataTypeMismatch)
             }
@@ -219,9 +370,87 @@ fn parse_single(lesson: ElementRef, timetable_type: &Type) -> Result<Lesson, Par
 }
 
 impl Lesson {
+    /// Whether lesson has homework assigned
+    #[must_use]
+    pub const fn has_homework(&self) -> bool {
+        matches!(
+            self,
+            Self::Regular {
+                homeworks: Some(_),
+                ..
+            } | Self::Substitution {
+                homeworks: Some(_),
+                ..
+            }
+        )
+    }
+
+    /// Get parsed group of lesson, raw text stays in `group` field
+    ///
+    /// `None` for whole-class lessons (missing or empty group).
+    #[must_use]
+    pub fn group(&self) -> Option<Group> {
+        match self {
+            Self::Regular { group, .. } | Self::Substitution { group, .. } => group
+                .as_deref()
+                .filter(|group| !group.trim().is_empty())
+                .map(Group::parse),
+            Self::Canceled { .. } | Self::Absent { .. } => None,
+        }
+    }
+
+    /// Get number of lesson's group (e.g. `1` for `1.sk (dívky)`), `None` for whole-class
+    /// lessons and groups that aren't numbered
+    #[must_use]
+    pub fn group_number(&self) -> Option<u8> {
+        self.group().and_then(|group| group.number)
+    }
+
+    /// Get key identifying lesson by subject, teacher and group
+    ///
+    /// Key isn't unique across timetable, it only stays the same when volatile
+    /// fields (room, topic, ...) change, so it can match lessons within one period.
+    /// Canceled lesson has the same key as the lesson it replaces, if details are known.
+    #[must_use]
+    pub fn slot_key(&self) -> String {
+        match self {
+            Self::Regular {
+                subject,
+                teacher,
+                group,
+                ..
+            }
+            | Self::Substitution {
+                subject,
+                teacher,
+                group,
+                ..
+            } => format!(
+                "{subject}|{teacher}|{}",
+                group.as_deref().unwrap_or_default()
+            ),
+            Self::Canceled {
+                subject, teacher, ..
+            } => format!(
+                "{}|{}|",
+                subject.as_deref().unwrap_or_default(),
+                teacher.as_deref().unwrap_or_default()
+            ),
+            Self::Absent { abbr, .. } => format!("absent|{abbr}|"),
+        }
+    }
+
+    /// Whether lesson takes place (regular or substitution)
+    #[must_use]
+    pub const fn is_held(&self) -> bool {
+        matches!(self, Self::Regular { .. } | Self::Substitution { .. })
+    }
+
     pub(crate) fn parse(
         lesson: ElementRef,
+        period: usize,
         timetable_type: &Type,
+        teacher_name: Option<&str>,
     ) -> Result<Vec<Self>, ParseError> {
         let item = lesson.select(&DAY_ITEM_SELECTOR).next();
         let Some(item) = item else {
@@ -230,9 +459,278 @@ impl Lesson {
 
         let lessons = item
             .select(&LESSON_SELECTOR)
-            .map(|lesson| parse_single(lesson, timetable_type))
+            .map(|lesson| parse_single(lesson, period, timetable_type, teacher_name))
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(lessons)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_single, Lesson, ParseError, Type};
+    use proptest::prelude::*;
+    use scraper::{Html, Selector};
+    use serde_json::{Map, Value};
+
+    /// Parse lesson from `div.day-item-hover` with given classes, `data-detail` and content
+    fn parse(classes: &str, detail: &str, content: &str, typ: &Type) -> Result<Lesson, ParseError> {
+        let detail = detail.replace('&', "&amp;").replace('"', "&quot;");
+        let html = Html::parse_fragment(&format!(
+            "<div class=\"day-item-hover {classes}\" data-detail=\"{detail}\">{content}</div>"
+        ));
+        let selector = Selector::parse("div.day-item-hover").unwrap();
+        let lesson = html.select(&selector).next().unwrap();
+        parse_single(lesson, 0, typ, None)
+    }
+
+    fn class() -> Type {
+        Type::Class("1.A".to_owned())
+    }
+
+    const CONTENT: &str = r#"<div class="middle">M</div><div class="bottom">Nov</div>"#;
+
+    #[test]
+    fn has_absent_string_false() {
+        let lesson = parse(
+            "",
+            r#"{"type":"atom","subjecttext":"Matematika","teacher":"Novák Jan","hasAbsent":"false"}"#,
+            CONTENT,
+            &class(),
+        )
+        .unwrap();
+        assert!(matches!(
+            lesson,
+            Lesson::Regular {
+                has_absent: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn notice_and_change_info() {
+        let lesson = parse(
+            "pink",
+            r#"{"type":"atom","subjecttext":"Matematika","teacher":"Novák Jan","notice":"Přineste kalkulačky","changeinfo":"Suplování (Dvořák)"}"#,
+            CONTENT,
+            &class(),
+        )
+        .unwrap();
+        let Lesson::Substitution {
+            notice,
+            change_info,
+            change,
+            ..
+        } = lesson
+        else {
+            panic!("expected substitution, got {lesson:?}");
+        };
+        assert_eq!(notice.as_deref(), Some("Přineste kalkulačky"));
+        assert_eq!(change_info.as_deref(), Some("Suplování (Dvořák)"));
+        let change = change.unwrap();
+        assert_eq!(change.reason.as_deref(), Some("Suplování"));
+        assert_eq!(change.original_teacher.as_deref(), Some("Dvořák"));
+    }
+
+    #[test]
+    fn absence_on_regular_lesson() {
+        let lesson = parse(
+            "",
+            r#"{"type":"atom","subjecttext":"Matematika","teacher":"Novák Jan","hasAbsent":true,"absencetext":"Nepřítomni: 2","absentInfoText":"Novotná, Svoboda"}"#,
+            CONTENT,
+            &class(),
+        )
+        .unwrap();
+        let Lesson::Regular {
+            has_absent,
+            absence_text,
+            absent_info_text,
+            ..
+        } = lesson
+        else {
+            panic!("expected regular lesson, got {lesson:?}");
+        };
+        assert!(has_absent);
+        assert_eq!(absence_text.as_deref(), Some("Nepřítomni: 2"));
+        assert_eq!(absent_info_text.as_deref(), Some("Novotná, Svoboda"));
+    }
+
+    #[test]
+    fn subject_and_abbr() {
+        let lesson = parse(
+            "",
+            r#"{"type":"atom","subjecttext":"Matematika | po 22.1. | 2 (8:55 - 9:40)","teacher":"Novák Jan"}"#,
+            CONTENT,
+            &class(),
+        )
+        .unwrap();
+        let Lesson::Regular {
+            subject,
+            abbr,
+            time,
+            ..
+        } = lesson
+        else {
+            panic!("expected regular lesson, got {lesson:?}");
+        };
+        assert_eq!(subject, "Matematika");
+        assert_eq!(abbr, "M");
+        let (start, end) = time.unwrap();
+        assert_eq!(start.to_string(), "08:55:00");
+        assert_eq!(end.to_string(), "09:40:00");
+    }
+
+    #[test]
+    fn remote_lesson_without_room() {
+        for detail in [
+            r#"{"type":"atom","subjecttext":"Matematika","teacher":"Novák Jan"}"#,
+            r#"{"type":"atom","subjecttext":"Matematika","teacher":"Novák Jan","room":""}"#,
+        ] {
+            let lesson = parse("", detail, CONTENT, &class()).unwrap();
+            assert!(matches!(lesson, Lesson::Regular { room: None, .. }));
+        }
+
+        let lesson = parse(
+            "",
+            r#"{"type":"atom","subjecttext":"Matematika","group":"1.A","room":""}"#,
+            CONTENT,
+            &Type::Teacher("UXYZ".to_owned()),
+        )
+        .unwrap();
+        assert!(matches!(lesson, Lesson::Regular { room: None, .. }));
+    }
+
+    /// Any JSON scalar, as fields of `data-detail` can have unexpected types
+    fn json_value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            any::<i64>().prop_map(Value::from),
+            prop_oneof![
+                Just(String::new()),
+                Just("true".to_owned()),
+                Just("Matematika | po 22.1. | 2 (8:55 - 9:40)".to_owned()),
+                ".*",
+            ]
+            .prop_map(Value::String),
+        ]
+    }
+
+    /// `data-detail` object with known or random type tag and any subset of known fields
+    fn detail() -> impl Strategy<Value = String> {
+        let tag = prop_oneof![
+            prop::sample::select(vec!["atom", "removed", "absent"]).prop_map(Value::from),
+            json_value(),
+        ];
+        let key = prop_oneof![
+            prop::sample::select(vec![
+                "subjecttext",
+                "teacher",
+                "room",
+                "group",
+                "theme",
+                "notice",
+                "changeinfo",
+                "homeworks",
+                "absencetext",
+                "hasAbsent",
+                "absentInfoText",
+                "cycle",
+                "InfoAbsentName",
+                "absentinfo",
+            ])
+            .prop_map(ToOwned::to_owned),
+            "[a-zA-Z]{1,12}",
+        ];
+        (
+            prop::option::of(tag),
+            prop::collection::vec((key, json_value()), 0..12),
+        )
+            .prop_map(|(tag, fields)| {
+                let mut object = fields.into_iter().collect::<Map<_, _>>();
+                if let Some(tag) = tag {
+                    object.insert("type".to_owned(), tag);
+                }
+                Value::Object(object).to_string()
+            })
+    }
+
+    /// Every `data-detail` from corpus parses to lesson of expected kind
+    #[test]
+    fn data_detail_corpus() {
+        for line in include_str!("../../../fixtures/data-detail.jsonl").lines() {
+            let entry = serde_json::from_str::<Value>(line).unwrap();
+            let typ = match entry["timetable"].as_str().unwrap() {
+                "teacher" => Type::Teacher("UXYZ".to_owned()),
+                "room" => Type::Room("R1".to_owned()),
+                _ => class(),
+            };
+            let classes = entry["classes"].as_str().unwrap();
+            let detail = entry["detail"].to_string();
+            let lesson = parse(classes, &detail, CONTENT, &typ)
+                .unwrap_or_else(|err| panic!("{detail}: {err}"));
+            let kind = match lesson {
+                Lesson::Regular { .. } => "regular",
+                Lesson::Substitution { .. } => "substitution",
+                Lesson::Canceled { .. } => "canceled",
+                Lesson::Absent { .. } => "absent",
+            };
+            assert_eq!(kind, entry["kind"].as_str().unwrap(), "{detail}");
+        }
+    }
+
+    #[test]
+    fn group_number() {
+        for (group, number, name, label) in [
+            (Some("1.sk"), Some(1), Some("Skupina 1"), None),
+            (Some("01.sk"), Some(1), Some("Skupina 1"), None),
+            (
+                Some("2.sk (dívky)"),
+                Some(2),
+                Some("Skupina 2"),
+                Some("dívky"),
+            ),
+            (Some("Sk3 (CH)"), Some(3), Some("Skupina 3"), Some("CH")),
+            (Some("AJ1"), None, Some("AJ1"), None),
+            (Some("300.sk"), None, Some("300.sk"), None),
+            (Some(""), None, None, None),
+            (Some("  "), None, None, None),
+            (None, None, None, None),
+        ] {
+            let detail = serde_json::json!({
+                "type": "atom",
+                "subjecttext": "Matematika",
+                "teacher": "Novák Jan",
+                "group": group,
+            });
+            let lesson = parse("", &detail.to_string(), CONTENT, &class()).unwrap();
+            let parsed = lesson.group();
+            assert_eq!(lesson.group_number(), number, "{group:?}");
+            assert_eq!(parsed.as_ref().map(|g| g.name.as_str()), name, "{group:?}");
+            assert_eq!(
+                parsed.as_ref().and_then(|g| g.label.as_deref()),
+                label,
+                "{group:?}"
+            );
+        }
+    }
+
+    proptest! {
+        /// Parser returns lesson or error for any `data-detail`, it never panics
+        #[test]
+        fn arbitrary_detail_doesnt_panic(
+            detail in detail(),
+            classes in prop::sample::select(vec!["", "pink", "green"]),
+            content in prop::sample::select(vec![CONTENT, "", r#"<div class="middle">M</div>"#]),
+            teacher in any::<bool>(),
+        ) {
+            let typ = if teacher {
+                Type::Teacher("UXYZ".to_owned())
+            } else {
+                class()
+            };
+            let _ = parse(classes, &detail, content, &typ);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+/// What was changed by substitution, parsed from its change info text
+///
+/// Parsing is best-effort, recognized formats are:
+/// - `Reason: Teacher (Subject)`, e.g. `Suplování: Novák Jan (Matematika)`
+/// - `Reason: Teacher`, e.g. `Suplování: Novák Jan`
+/// - `Reason (Teacher)`, e.g. `Suplování (Novák)`
+/// - `Teacher`, e.g. `Novák Jan`
+///
+/// Parts that can't be recognized are `None`, raw text is always kept.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeInfo {
+    /// Whole change info text
+    pub raw: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_teacher: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl ChangeInfo {
+    /// Parse change info text
+    #[must_use]
+    pub fn parse(raw: &str) -> Self {
+        let non_empty = |s: &str| {
+            let s = s.trim();
+            (!s.is_empty()).then(|| s.to_owned())
+        };
+
+        let parenthesized = |s: &str| non_empty(s.trim_end().trim_end_matches(')'));
+
+        let (reason, original_teacher, original_subject) = match raw.split_once(':') {
+            Some((reason, detail)) => match detail.split_once('(') {
+                Some((teacher, subject)) => (
+                    non_empty(reason),
+                    non_empty(teacher),
+                    parenthesized(subject),
+                ),
+                None => (non_empty(reason), non_empty(detail), None),
+            },
+            None => match raw.split_once('(') {
+                Some((reason, teacher)) => (non_empty(reason), parenthesized(teacher), None),
+                None => (None, non_empty(raw), None),
+            },
+        };
+
+        Self {
+            raw: raw.trim().to_owned(),
+            original_teacher,
+            original_subject,
+            reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChangeInfo;
+
+    fn parts(info: &ChangeInfo) -> (Option<&str>, Option<&str>, Option<&str>) {
+        (
+            info.reason.as_deref(),
+            info.original_teacher.as_deref(),
+            info.original_subject.as_deref(),
+        )
+    }
+
+    #[test]
+    fn reason_teacher_subject() {
+        let info = ChangeInfo::parse("Suplování: Novák Jan (Matematika)");
+        assert_eq!(
+            parts(&info),
+            (Some("Suplování"), Some("Novák Jan"), Some("Matematika"))
+        );
+        assert_eq!(info.raw, "Suplování: Novák Jan (Matematika)");
+    }
+
+    #[test]
+    fn reason_teacher() {
+        let info = ChangeInfo::parse("Suplování: Novák Jan");
+        assert_eq!(parts(&info), (Some("Suplování"), Some("Novák Jan"), None));
+    }
+
+    #[test]
+    fn reason_parenthesized_teacher() {
+        let info = ChangeInfo::parse("Suplování (Novák)");
+        assert_eq!(parts(&info), (Some("Suplování"), Some("Novák"), None));
+    }
+
+    #[test]
+    fn teacher_only() {
+        let info = ChangeInfo::parse(" Novák Jan ");
+        assert_eq!(parts(&info), (None, Some("Novák Jan"), None));
+        assert_eq!(info.raw, "Novák Jan");
+    }
+
+    #[test]
+    fn empty_parts() {
+        let info = ChangeInfo::parse(": ()");
+        assert_eq!(parts(&info), (None, None, None));
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+/// Group of lesson parsed from its free-form text (e.g. `Skupina 1 (CH)`)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+pub struct Group {
+    /// Normalized name, same group written differently (`Sk1`, `sk. 1`, `1. skupina`)
+    /// results in the same name (`Skupina 1`)
+    pub name: String,
+    /// Number of numbered group (e.g. `1` for `1.sk`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<u8>,
+    /// Text in parentheses after the name (e.g. `CH`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Group {
+    /// Parse group from text of lesson's group field
+    #[must_use]
+    pub fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+
+        let (name, label) = match raw.split_once('(') {
+            Some((name, label)) => {
+                let label = label.trim_end_matches(')').trim();
+                (name.trim(), (!label.is_empty()).then(|| label.to_owned()))
+            }
+            None => (raw, None),
+        };
+
+        let number = number(name);
+        let name = number.map_or_else(|| name.to_owned(), |num| format!("Skupina {num}"));
+        Self {
+            name,
+            number,
+            label,
+        }
+    }
+}
+
+/// Parse number of group written in common ways (`1.sk`, `Sk1`, ...), names without
+/// number are kept as they are
+fn number(name: &str) -> Option<u8> {
+    let lower = name.to_lowercase();
+
+    // `1. skupina`, `1. sk.`
+    if let Some((num, rest)) = lower.split_once('.') {
+        let rest = rest.trim();
+        if rest == "skupina" || rest == "sk" || rest == "sk." {
+            if let Some(num) = parse_number(num.trim()) {
+                return Some(num);
+            }
+        }
+    }
+
+    // `Skupina 1`, `Sk1`, `sk. 1`
+    ["skupina", "sk.", "sk"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .and_then(|num| parse_number(num.trim()))
+}
+
+/// Parse number made only of digits (`str::parse` would also accept sign)
+fn parse_number(s: &str) -> Option<u8> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Group;
+    use serde_json::json;
+
+    #[test]
+    fn same_group_written_differently() {
+        let group = Group::parse("Skupina 1");
+        for raw in ["Sk1", "sk. 1", "1. skupina", "1.sk", " SKUPINA 1 "] {
+            assert_eq!(Group::parse(raw), group, "{raw}");
+        }
+        assert_ne!(Group::parse("Sk2"), group);
+    }
+
+    #[test]
+    fn serialize_omits_missing() {
+        assert_eq!(
+            serde_json::to_value(Group::parse("AJ1")).unwrap(),
+            json!({ "name": "AJ1" })
+        );
+        assert_eq!(
+            serde_json::to_value(Group::parse("1.sk (CH)")).unwrap(),
+            json!({ "name": "Skupina 1", "number": 1, "label": "CH" })
+        );
+        let group = Group::parse("Sk1");
+        let json = serde_json::to_string(&group).unwrap();
+        assert_eq!(serde_json::from_str::<Group>(&json).unwrap(), group);
+    }
+}
@@ -1,49 +1,141 @@
 use once_cell::sync::Lazy;
 use scraper::{ElementRef, Selector};
 
-use crate::modules::timetable::Type;
+use crate::modules::timetable::{util::date_without_year, Type};
+use chrono::{NaiveDate, NaiveTime};
 
-use super::{get_prop, ParseError};
+use super::ParseError;
 
 type ParseResult<T> = Result<T, ParseError>;
 
+/// Subject, date and time of lesson parsed from subjecttext
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SubjectMeta {
+    pub subject: String,
+    pub date: Option<NaiveDate>,
+    /// Start and end of lesson
+    pub time: Option<(NaiveTime, NaiveTime)>,
+}
+
 /// Parse subject from subjecttext
-pub fn subject(s: Option<String>) -> ParseResult<String> {
+///
+/// Subjecttext is usually `Subject | date | hour`, but it can also be just the subject.
+pub fn subject(s: Option<String>) -> ParseResult<SubjectMeta> {
     let subjecttext = s.ok_or(ParseError::MissingProperty("subjecttext"))?;
+    subject_meta(&subjecttext)
+}
 
-    let (subject, _) = subjecttext
-        .split_once(" | ")
-        .ok_or_else(|| ParseError::BadSubjectText(subjecttext.clone()))?;
+/// Parse subject, date and time of lesson from subjecttext
+///
+/// E.g. `Český jazyk a literatura | po 22.1. | 2 (8:55 - 9:40)`, date and time
+/// are `None` if their segment is missing (permanent timetables often omit the date).
+pub fn subject_meta(s: &str) -> ParseResult<SubjectMeta> {
+    let mut parts = s.split(" | ");
+    let subject = parts.next().unwrap_or_default().trim();
     if subject.is_empty() {
-        return Err(ParseError::BadSubjectText(subjecttext.clone()));
+        return Err(ParseError::BadSubjectText(s.to_owned()));
     }
-    Ok(subject.trim().to_owned())
+
+    let mut date = None;
+    let mut times = None;
+    for part in parts {
+        // `2 (8:55 - 9:40)`
+        if let Some((_, range)) = part.split_once('(') {
+            times = range
+                .trim_end()
+                .trim_end_matches(')')
+                .split_once('-')
+                .and_then(|(from, to)| {
+                    let from = NaiveTime::parse_from_str(from.trim(), "%H:%M").ok()?;
+                    let to = NaiveTime::parse_from_str(to.trim(), "%H:%M").ok()?;
+                    Some((from, to))
+                });
+        // `po 22.1.`
+        } else if part.contains('.') {
+            let part = part.trim();
+            date = date_without_year(part.rsplit_once(' ').map_or(part, |(_, date)| date));
+        }
+    }
+
+    Ok(SubjectMeta {
+        subject: subject.to_owned(),
+        date,
+        time: times,
+    })
 }
 
 static TEACHER_ABBR_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("div.bottom").unwrap());
 
 /// Parse teacher
+///
+/// Returns display name and abbreviation of the teacher, regardless of timetable type,
+/// and abbreviations of other teachers if lesson has more of them.
+/// Teacher timetables don't show the teacher in the cell, so the name falls back to
+/// `teacher_name` (taken from the page header) and then to the selector id,
+/// and the abbreviation is `None`.
 pub fn teacher(
     lesson: ElementRef,
     teacher: Option<String>,
     timetable_type: &Type,
-) -> ParseResult<(String, Option<String>)> {
-    let teacher = match teacher {
-        Some(t) => t,
-        None => {
-            if let Type::Teacher(t) = timetable_type {
-                t.to_owned()
-            } else {
-                return Err(ParseError::MissingProperty("teacher"));
-            }
-        }
-    };
+    teacher_name: Option<&str>,
+) -> ParseResult<(String, Option<String>, Vec<String>)> {
+    if let Type::Teacher(id) = timetable_type {
+        let teacher = teacher
+            .or_else(|| teacher_name.map(ToOwned::to_owned))
+            .unwrap_or_else(|| id.to_owned());
+        return Ok((teacher, None, Vec::new()));
+    }
 
-    let teacher_abbr = if let Type::Teacher(_) = timetable_type {
-        None
-    } else {
-        Some(get_prop(lesson, &TEACHER_ABBR_SELECTOR, "teacher_abbr")?)
-    };
+    let teacher = teacher.ok_or(ParseError::MissingProperty("teacher"))?;
+    let mut abbrs = lesson
+        .select(&TEACHER_ABBR_SELECTOR)
+        .map(|elem| elem.text().collect::<String>().trim().to_owned())
+        .filter(|abbr| !abbr.is_empty());
+    let teacher_abbr = abbrs
+        .next()
+        .ok_or(ParseError::MissingProperty("teacher_abbr"))?;
 
-    Ok((teacher, teacher_abbr))
+    Ok((teacher, Some(teacher_abbr), abbrs.collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::teacher;
+    use crate::modules::timetable::Type;
+    use scraper::{Html, Selector};
+
+    /// Parse teacher of `div.day-item-hover` with given content
+    fn parse_teacher(
+        content: &str,
+        name: Option<&str>,
+        typ: &Type,
+    ) -> (String, Option<String>, Vec<String>) {
+        let html = Html::parse_fragment(&format!("<div class=\"day-item-hover\">{content}</div>"));
+        let selector = Selector::parse("div.day-item-hover").unwrap();
+        let lesson = html.select(&selector).next().unwrap();
+        teacher(lesson, name.map(ToOwned::to_owned), typ, None).unwrap()
+    }
+
+    #[test]
+    fn multiple_teachers() {
+        let (name, abbr, additional) = parse_teacher(
+            r#"<div class="bottom">Nov</div><div class="bottom">Dvo</div>"#,
+            Some("Novák Jan, Dvořák Petr"),
+            &Type::Class("1.A".to_owned()),
+        );
+        assert_eq!(name, "Novák Jan, Dvořák Petr");
+        assert_eq!(abbr.as_deref(), Some("Nov"));
+        assert_eq!(additional, ["Dvo"]);
+    }
+
+    #[test]
+    fn single_teacher() {
+        let (_, abbr, additional) = parse_teacher(
+            r#"<div class="bottom"> Nov </div>"#,
+            Some("Novák Jan"),
+            &Type::Room("101".to_owned()),
+        );
+        assert_eq!(abbr.as_deref(), Some("Nov"));
+        assert!(additional.is_empty());
+    }
 }
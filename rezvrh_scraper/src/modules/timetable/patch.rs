@@ -0,0 +1,185 @@
+use super::{day::Day, hour::Hour, Lesson, Timetable};
+use serde::{Deserialize, Serialize};
+
+/// Changed lessons in one cell of timetable
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CellPatch {
+    pub day: usize,
+    pub hour: usize,
+    pub lessons: Vec<Lesson>,
+}
+
+/// Changes between two versions of timetable
+///
+/// If days don't match (different count, dates, events or number of periods), all days
+/// are sent instead of cells.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TimetablePatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hours: Option<Vec<Hour>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days: Option<Vec<Day>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cells: Vec<CellPatch>,
+}
+
+impl TimetablePatch {
+    /// Whether there are no changes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hours.is_none() && self.days.is_none() && self.cells.is_empty()
+    }
+
+    /// Apply patch to previous version of timetable
+    #[must_use]
+    pub fn apply(&self, previous: &Timetable) -> Timetable {
+        let mut timetable = previous.clone();
+        if let Some(hours) = &self.hours {
+            timetable.hours.clone_from(hours);
+        }
+        if let Some(days) = &self.days {
+            timetable.days.clone_from(days);
+        }
+        for cell in &self.cells {
+            let Some(day) = timetable.days.get_mut(cell.day) else {
+                continue;
+            };
+            if day.lessons.len() <= cell.hour {
+                day.lessons.resize_with(cell.hour + 1, Vec::new);
+            }
+            day.lessons[cell.hour].clone_from(&cell.lessons);
+        }
+        timetable
+    }
+}
+
+impl Timetable {
+    /// Get changes from previous version of timetable
+    #[must_use]
+    pub fn diff_patch(&self, previous: &Self) -> TimetablePatch {
+        let hours = (self.hours != previous.hours).then(|| self.hours.clone());
+
+        let same_days = self.days.len() == previous.days.len()
+            && self.days.iter().zip(&previous.days).all(|(new, old)| {
+                new.date == old.date
+                    && new.weekday == old.weekday
+                    && new.lessons.len() == old.lessons.len()
+                    && new.events == old.events
+            });
+        if !same_days {
+            return TimetablePatch {
+                hours,
+                days: Some(self.days.clone()),
+                cells: Vec::new(),
+            };
+        }
+
+        let mut cells = Vec::new();
+        for (d, (new, old)) in self.days.iter().zip(&previous.days).enumerate() {
+            for (h, (lessons, old)) in new.lessons.iter().zip(&old.lessons).enumerate() {
+                if lessons != old {
+                    cells.push(CellPatch {
+                        day: d,
+                        hour: h,
+                        lessons: lessons.clone(),
+                    });
+                }
+            }
+        }
+
+        TimetablePatch {
+            hours,
+            days: None,
+            cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Day, Event, Hour, Lesson, Timetable};
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn lesson(period: usize, subject: &str) -> Lesson {
+        Lesson::Canceled {
+            period,
+            subject: Some(subject.to_owned()),
+            teacher: None,
+            room: None,
+        }
+    }
+
+    fn timetable(days: Vec<Vec<Vec<Lesson>>>) -> Timetable {
+        let hours = (0..3)
+            .map(|num| {
+                Hour::new(
+                    num + 1,
+                    NaiveTime::from_hms_opt(8 + num as u32, 0, 0).unwrap(),
+                    45,
+                )
+            })
+            .collect();
+        let monday = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let days = days
+            .into_iter()
+            .enumerate()
+            .map(|(i, lessons)| Day::new(monday + chrono::Duration::days(i as i64), lessons))
+            .collect();
+        Timetable::new(hours, days)
+    }
+
+    fn assert_round_trip(old: &Timetable, new: &Timetable) {
+        assert_eq!(&new.diff_patch(old).apply(old), new);
+    }
+
+    #[test]
+    fn unchanged() {
+        let old = timetable(vec![vec![vec![lesson(0, "M")]]]);
+        assert!(old.diff_patch(&old).is_empty());
+    }
+
+    #[test]
+    fn changed_cell() {
+        let old = timetable(vec![vec![vec![lesson(0, "M")], vec![lesson(1, "F")]]]);
+        let new = timetable(vec![vec![vec![lesson(0, "M")], vec![lesson(1, "Ch")]]]);
+        let patch = new.diff_patch(&old);
+        assert!(patch.days.is_none());
+        assert_eq!(patch.cells.len(), 1);
+        assert_round_trip(&old, &new);
+    }
+
+    #[test]
+    fn shrunk_day() {
+        let old = timetable(vec![
+            vec![vec![lesson(0, "M")], vec![lesson(1, "F")]],
+            vec![vec![lesson(0, "D")]],
+        ]);
+        let new = timetable(vec![vec![vec![lesson(0, "M")]], vec![vec![lesson(0, "D")]]]);
+        assert_round_trip(&old, &new);
+    }
+
+    #[test]
+    fn grown_day() {
+        let old = timetable(vec![vec![vec![lesson(0, "M")]], vec![]]);
+        let new = timetable(vec![
+            vec![vec![lesson(0, "M")], vec![], vec![lesson(2, "F")]],
+            vec![],
+        ]);
+        assert_round_trip(&old, &new);
+    }
+
+    #[test]
+    fn new_event() {
+        let old = timetable(vec![vec![vec![lesson(0, "M")]]]);
+        let mut new = old.clone();
+        new.days[0].events.push(Event {
+            name: "Exkurze".to_owned(),
+            info: None,
+        });
+        let patch = new.diff_patch(&old);
+        assert!(!patch.is_empty());
+        assert_round_trip(&old, &new);
+    }
+}
@@ -0,0 +1,74 @@
+use super::{Lesson, Timetable};
+
+/// Short label of lessons in one cell
+fn cell_label(lessons: &[Lesson]) -> String {
+    lessons
+        .iter()
+        .map(|lesson| match lesson {
+            Lesson::Regular { abbr, .. } | Lesson::Absent { abbr, .. } => abbr.to_owned(),
+            Lesson::Substitution { abbr, .. } => format!("{abbr}*"),
+            Lesson::Canceled { .. } => "-".to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Timetable {
+    /// Render timetable as text grid with days as rows and hours as columns
+    ///
+    /// Cells show subject abbreviations, substitutions are marked with `*`
+    /// and canceled lessons with `-`
+    #[must_use]
+    pub fn to_ascii(&self) -> String {
+        let header = std::iter::once(String::new()).chain(
+            self.hours
+                .iter()
+                .map(|hour| format!("{} {}", hour.num, hour.start.format("%H:%M"))),
+        );
+        let mut rows = vec![header.collect::<Vec<_>>()];
+        for (i, day) in self.days.iter().enumerate() {
+            let label = match (day.date, day.weekday) {
+                (Some(date), _) => date.format("%a %d.%m.").to_string(),
+                (None, Some(weekday)) => weekday.to_string(),
+                (None, None) => (i + 1).to_string(),
+            };
+            let cells = (0..self.hours.len()).map(|h| {
+                day.lessons
+                    .get(h)
+                    .map_or_else(String::new, |lessons| cell_label(lessons))
+            });
+            rows.push(std::iter::once(label).chain(cells).collect());
+        }
+
+        let widths = (0..=self.hours.len())
+            .map(|c| {
+                rows.iter()
+                    .map(|row| row[c].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        let separator = widths.iter().fold(String::from("+"), |mut line, width| {
+            line.push_str(&"-".repeat(width + 2));
+            line.push('+');
+            line
+        });
+
+        let mut out = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            // Line above header and between header and days
+            if i < 2 {
+                out.push_str(&separator);
+                out.push('\n');
+            }
+            out.push('|');
+            for (cell, width) in row.iter().zip(&widths) {
+                out.push_str(&format!(" {cell:<width$} |"));
+            }
+            out.push('\n');
+        }
+        out.push_str(&separator);
+        out.push('\n');
+        out
+    }
+}
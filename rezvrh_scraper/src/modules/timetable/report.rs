@@ -0,0 +1,62 @@
+use super::{Hour, Lesson, Timetable};
+use chrono::{NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Teaching load of one day
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DayHours {
+    pub date: Option<NaiveDate>,
+    pub weekday: Option<Weekday>,
+    /// Number of periods with held lesson
+    pub lessons: usize,
+    /// Total duration of these periods in minutes
+    pub minutes: u32,
+}
+
+/// Teaching load summary of timetable, one row per day
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HoursReport {
+    pub days: Vec<DayHours>,
+    pub total_lessons: usize,
+    pub total_minutes: u32,
+}
+
+impl Timetable {
+    /// Summarize number and duration of taught lessons per day
+    ///
+    /// Period counts once even with multiple lessons, canceled lessons and absences
+    /// are excluded. Durations are taken from `hours` by index (periods without hour count 0 minutes).
+    #[must_use]
+    pub fn to_hours_report(&self, hours: &[Hour]) -> HoursReport {
+        let days = self
+            .days
+            .iter()
+            .map(|day| {
+                let taught = day
+                    .lessons
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, lessons)| lessons.iter().any(Lesson::is_held))
+                    .map(|(h, _)| h);
+                let (lessons, minutes) = taught.fold((0, 0), |(count, minutes), h| {
+                    let duration = hours.get(h).map_or(0, |hour| hour.duration);
+                    (count + 1, minutes + duration)
+                });
+                DayHours {
+                    date: day.date,
+                    weekday: day.weekday,
+                    lessons,
+                    minutes,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        HoursReport {
+            total_lessons: days.iter().map(|day| day.lessons).sum(),
+            total_minutes: days.iter().map(|day| day.minutes).sum(),
+            days,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Lesson, Timetable};
+
+/// Whether substitution has the same class, subject, teacher and group as usual lesson
+fn same_as(substitution: &Lesson, usual: &Lesson) -> bool {
+    match (substitution, usual) {
+        (
+            Lesson::Substitution {
+                class,
+                subject,
+                teacher,
+                group,
+                ..
+            },
+            Lesson::Regular {
+                class: usual_class,
+                subject: usual_subject,
+                teacher: usual_teacher,
+                group: usual_group,
+                ..
+            },
+        ) => {
+            class == usual_class
+                && subject == usual_subject
+                && teacher == usual_teacher
+                && group == usual_group
+        }
+        _ => false,
+    }
+}
+
+impl Timetable {
+    /// Turn substitutions that don't change anything important into regular lessons
+    ///
+    /// Substitution is demoted when lesson in the same period of permanent timetable
+    /// has the same class, subject, teacher and group (e.g. only room was swapped).
+    /// Days are matched by weekday, periods by index.
+    pub fn simplify_substitutions(&mut self, permanent: &Self) {
+        for day in &mut self.days {
+            let Some(usual) = day
+                .weekday
+                .and_then(|weekday| permanent.day_by_weekday(weekday))
+            else {
+                continue;
+            };
+            for (lessons, usual) in day.lessons.iter_mut().zip(&usual.lessons) {
+                for lesson in lessons {
+                    if !usual.iter().any(|usual| same_as(lesson, usual)) {
+                        continue;
+                    }
+                    if let Lesson::Substitution {
+                        period,
+                        class,
+                        subject,
+                        abbr,
+                        date,
+                        time,
+                        teacher,
+                        teacher_abbr,
+                        additional_teachers,
+                        room,
+                        group,
+                        topic,
+                        homeworks,
+                        cycle,
+                        notice,
+                        change_info,
+                        has_absent,
+                        absence_text,
+                        absent_info_text,
+                        change: _,
+                    } = lesson.clone()
+                    {
+                        *lesson = Lesson::Regular {
+                            period,
+                            class,
+                            subject,
+                            abbr,
+                            date,
+                            time,
+                            teacher,
+                            teacher_abbr,
+                            additional_teachers,
+                            room,
+                            group,
+                            topic,
+                            homeworks,
+                            cycle,
+                            notice,
+                            change_info,
+                            has_absent,
+                            absence_text,
+                            absent_info_text,
+                        };
+                    }
+                }
+            }
+        }
+    }
+}
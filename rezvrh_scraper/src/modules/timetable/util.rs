@@ -1,3 +1,4 @@
+use chrono::{Datelike, NaiveDate};
 use serde::{de::IntoDeserializer, Deserialize};
 
 pub fn empty_string_as_none<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
@@ -13,6 +14,28 @@ where
     }
 }
 
+/// Deserialize bool from JSON boolean or its string form (`"true"`/`"false"`)
+pub fn bool_or_string<'de, D>(de: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    match BoolOrString::deserialize(de)? {
+        BoolOrString::Bool(b) => Ok(b),
+        BoolOrString::String(s) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" => Ok(true),
+            "false" | "" => Ok(false),
+            _ => Err(serde::de::Error::custom(format!("invalid bool: {s}"))),
+        },
+    }
+}
+
 pub fn single_iter<T, I, E, F>(mut iter: I, err: F) -> Result<T, E>
 where
     I: Iterator<Item = T>,
@@ -24,3 +47,25 @@ where
     }
     Ok(first)
 }
+
+/// Parse date without year (e.g. `12.6.`), picking year so that date is near today
+pub fn date_without_year(date: &str) -> Option<NaiveDate> {
+    // Current year
+    let now = chrono::Local::now().date_naive();
+    let year = now.year();
+    let (day, month) = date.trim().split_once('.')?;
+    let (month, _) = month.split_once('.')?;
+    let date = NaiveDate::from_ymd_opt(year, month.trim().parse().ok()?, day.trim().parse().ok()?)?;
+
+    // Check diff by months
+    let diff = date - now;
+    if diff.num_days() < -60 {
+        // Next year
+        NaiveDate::from_ymd_opt(year + 1, date.month(), date.day())
+    } else if diff.num_days() > 60 {
+        // Last year
+        NaiveDate::from_ymd_opt(year - 1, date.month(), date.day())
+    } else {
+        Some(date)
+    }
+}
@@ -0,0 +1,28 @@
+//! Common types for typical usage
+//!
+//! ```no_run
+//! use rezvrh_scraper::prelude::*;
+//!
+//! # async fn example() -> Result<(), Error> {
+//! let client = Client::new("https://bakalari.example.com/".parse().unwrap());
+//! let bakalari = Bakalari::connect_with_client(client, AuthInput::None).await?;
+//!
+//! let selector: Selector = bakalari.get_selector(Type::Class, "1.A").unwrap();
+//! let timetable: Timetable = bakalari.get_timetable(Which::Actual, &selector).await?;
+//! for day in &timetable.days {
+//!     let day: &Day = day;
+//!     for (hour, lessons) in timetable.hours.iter().zip(&day.lessons) {
+//!         let hour: &Hour = hour;
+//!         for lesson in lessons {
+//!             let lesson: &Lesson = lesson;
+//!             println!("{:?} {}: {lesson:?}", day.date, hour.start);
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::{
+    AuthInput, Bakalari, Client, Day, Error, Hour, Lesson, Selector, Timetable, Type, Which,
+};